@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::raytracer::config::RenderSettings;
+
+/// A simple raytracer based on Ray Tracing in One Weekend.
+#[derive(Parser)]
+#[command(version)]
+pub struct Args {
+    /// Scene file to render (.toml or .json) [default: random spheres]
+    #[arg(long)]
+    pub scene: Option<PathBuf>,
+
+    /// Image width in pixels [default: 1200]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub width: Option<u32>,
+
+    /// Image height in pixels [default: 675]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub height: Option<u32>,
+
+    /// Samples per pixel [default: 500]
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub samples: Option<u32>,
+
+    /// Maximum number of bounces per path [default: 50]
+    #[arg(short = 'd', long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_depth: Option<u32>,
+
+    /// Render with sampled wavelengths instead of RGB, for dispersion [default: off]
+    #[arg(long)]
+    pub spectral: bool,
+
+    /// Number of render threads [default: one per core]
+    #[arg(short = 'j', long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub threads: Option<u32>,
+
+    /// Seed for scene generation and sampling [default: random]
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Image file to write (.png, .ppm, .pfm, .exr or .hdr)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Show the image in a window [default: only without --output]
+    #[arg(long, overrides_with = "no_viewer")]
+    pub viewer: bool,
+
+    /// Do not show the image in a window
+    #[arg(long, overrides_with = "viewer")]
+    pub no_viewer: bool,
+}
+
+impl Args {
+    /// Settings given on the command line, overriding those of the scene.
+    pub fn render_settings(&self) -> RenderSettings {
+        RenderSettings {
+            width: self.width,
+            height: self.height,
+            samples: self.samples,
+            max_depth: self.max_depth,
+            spectral: self.spectral.then_some(true),
+        }
+    }
+
+    pub fn viewer(&self) -> bool {
+        if self.viewer {
+            true
+        } else if self.no_viewer {
+            false
+        } else {
+            self.output.is_none()
+        }
+    }
+}
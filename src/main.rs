@@ -1,16 +1,61 @@
+mod cli;
 mod raytracer;
 
 use std::sync::Arc;
 
-use rand::Rng;
+use clap::Parser;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use raytracer::camera::Camera;
 use raytracer::color::Color;
-use raytracer::config::Config;
+use raytracer::config::{Config, RenderSettings};
+use raytracer::environment::Environment;
 use raytracer::materials::*;
+use raytracer::output::ImageFormat;
 use raytracer::ray::Hittable;
+use raytracer::scene;
 use raytracer::sphere::Sphere;
 use raytracer::vec3::*;
+use raytracer::world::World;
 
 pub fn main() -> Result<(), String> {
+    let args = cli::Args::parse();
+
+    // fail before rendering rather than after
+    if let Some(path) = &args.output {
+        ImageFormat::from_path(path)?;
+    }
+    if args.output.is_none() && !args.viewer() {
+        return Err("nothing to do, pass --output or drop --no-viewer".to_string());
+    }
+    if args.viewer() && !cfg!(feature = "viewer") {
+        return Err("built without the `viewer` feature, use an output file instead".to_string());
+    }
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads as usize)
+            .build_global()
+            .map_err(|e| e.to_string())?;
+    }
+
+    let seed = args.seed.unwrap_or_else(|| {
+        let seed = rand::random();
+        eprintln!("seed: {}", seed);
+        seed
+    });
+
+    let mut config = match &args.scene {
+        Some(path) => scene::load(path, args.render_settings())?,
+        None => random_spheres(args.render_settings(), seed),
+    };
+    config.viewer = args.viewer();
+    config.output = args.output;
+    config.seed = seed;
+    raytracer::run(&config)
+}
+
+/// The final scene of Ray Tracing in One Weekend.
+fn random_spheres(settings: RenderSettings, seed: u64) -> Config {
     let mut world = Vec::<Box<dyn Hittable>>::new();
 
     world.push(Box::new(Sphere::new(
@@ -21,7 +66,7 @@ pub fn main() -> Result<(), String> {
         )))),
     )));
 
-    let mut rng = rand::thread_rng();
+    let mut rng = SmallRng::seed_from_u64(seed);
 
     for a in -11..11 {
         for b in -11..11 {
@@ -81,18 +126,32 @@ pub fn main() -> Result<(), String> {
         vec3!(-4.0, 1.0, 0.0),
         1.0,
         Arc::new(Material::Lambertian(Lambertian::new(Color::new(
-            0.4 as f64, 0.2 as f64, 0.1 as f64,
+            0.4, 0.2, 0.1,
         )))),
     )));
     world.push(Box::new(Sphere::new(
         vec3!(4.0, 1.0, 0.0),
         1.0,
-        Arc::new(Material::Metal(Metal::new(
-            Color::new(0.7 as f64, 0.6 as f64, 0.5 as f64),
-            0.0,
-        ))),
+        Arc::new(Material::Metal(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0))),
     )));
 
-    let config = Config::new("Raytracer", 1200, 675, world);
-    raytracer::run(&config)
+    let camera = Camera::new(
+        settings.width(),
+        settings.height(),
+        vec3!(13.0, 2.0, 3.0),
+        vec3!(0.0, 0.0, 0.0),
+        vec3!(0.0, 1.0, 0.0),
+        20.0,
+        settings.samples(),
+        settings.max_depth(),
+    )
+    .with_aperture(0.1)
+    .with_focus_distance(10.0)
+    .with_spectral(settings.spectral());
+
+    Config::new(
+        "Raytracer",
+        camera,
+        World::new(world, Environment::default()),
+    )
 }
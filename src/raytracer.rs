@@ -1,73 +1,59 @@
-extern crate sdl2;
-
-use rayon::prelude::*;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::PixelFormatEnum;
-
+pub mod aabb;
+pub mod bump;
+pub mod bvh;
 pub mod camera;
 pub mod color;
 pub mod config;
+pub mod distribution;
+pub mod environment;
+pub mod framebuffer;
+pub mod input;
+pub mod instance;
 pub mod interval;
 pub mod materials;
+pub mod microfacet;
+pub mod mipmap;
+pub mod motion;
+pub mod obj;
+pub mod onb;
+pub mod output;
 pub mod ray;
+pub mod scene;
+pub mod spectrum;
 pub mod sphere;
+pub mod texture;
+pub mod transform;
+pub mod triangle;
 pub mod vec3;
+#[cfg(feature = "viewer")]
+pub mod viewer;
+pub mod world;
 
 use config::Config;
+use framebuffer::Framebuffer;
 
 pub fn run(config: &Config) -> Result<(), String> {
-    let sdl_context = sdl2::init()?;
-    let video_subsystem = sdl_context.video()?;
-
-    let window = video_subsystem
-        .window("Raytracer", config.width as u32, config.height as u32)
-        .position_centered()
-        .opengl()
-        .build()
-        .map_err(|e| e.to_string())?;
-
-    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
-    let texture_creator = canvas.texture_creator();
-
-    let mut texture = texture_creator
-        .create_texture_streaming(
-            PixelFormatEnum::RGB24,
-            config.width as u32,
-            config.height as u32,
-        )
-        .map_err(|e| e.to_string())?;
-
-    texture.with_lock(None, |buffer: &mut [u8], _| {
-        let bands: Vec<(usize, &mut [u8])> = buffer
-            .chunks_mut(config.camera.img_width * 3)
-            .enumerate()
-            .collect();
-
-        bands.into_par_iter().for_each_init(
-            || rand::thread_rng(),
-            |rng, (y, band)| config.camera.render(y, band, &config.world, rng),
-        );
-    })?;
+    let (width, height) = config.camera.output_size();
+    let mut framebuffer = Framebuffer::new(width, height);
+    framebuffer.render(&config.camera, &config.world, config.seed);
 
-    let mut event_pump = sdl_context.event_pump()?;
-
-    'running: loop {
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => break 'running,
-                _ => {}
-            }
-        }
+    if let Some(path) = &config.output {
+        output::save(&framebuffer, path)?;
+    }
 
-        canvas.clear();
-        canvas.copy(&texture, None, None)?;
-        canvas.present();
+    if config.viewer {
+        show(config, &framebuffer)?;
     }
 
     Ok(())
 }
+
+#[cfg(feature = "viewer")]
+fn show(config: &Config, framebuffer: &Framebuffer) -> Result<(), String> {
+    viewer::show(config.title, framebuffer)
+}
+
+#[cfg(not(feature = "viewer"))]
+fn show(_config: &Config, _framebuffer: &Framebuffer) -> Result<(), String> {
+    Err("built without the `viewer` feature, use an output file instead".to_string())
+}
@@ -0,0 +1,113 @@
+use super::interval::{self, Interval};
+use super::ray::Ray;
+use super::vec3::Vec3;
+
+/// Axis-aligned bounding box.
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+pub const EMPTY: Aabb = Aabb {
+    x: interval::EMPTY,
+    y: interval::EMPTY,
+    z: interval::EMPTY,
+};
+
+impl Aabb {
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Box spanned by two corner points in any order.
+    pub fn from_points(a: Vec3, b: Vec3) -> Self {
+        Self::new(
+            Interval::new(a.x.min(b.x), a.x.max(b.x)),
+            Interval::new(a.y.min(b.y), a.y.max(b.y)),
+            Interval::new(a.z.min(b.z), a.z.max(b.z)),
+        )
+    }
+
+    /// Smallest box containing both `a` and `b`.
+    pub fn enclosing(a: &Aabb, b: &Aabb) -> Self {
+        Self::new(
+            Interval::enclosing(&a.x, &b.x),
+            Interval::enclosing(&a.y, &b.y),
+            Interval::enclosing(&a.z, &b.z),
+        )
+    }
+
+    /// Gives flat sides a minimal thickness so the slab test can still hit them.
+    pub fn padded(&self) -> Self {
+        let delta = 1.0e-4;
+        let pad = |i: &Interval| {
+            if i.size() < delta {
+                i.expand(delta)
+            } else {
+                *i
+            }
+        };
+        Self::new(pad(&self.x), pad(&self.y), pad(&self.z))
+    }
+
+    pub fn axis(&self, n: usize) -> &Interval {
+        match n {
+            0 => &self.x,
+            1 => &self.y,
+            _ => &self.z,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.size() < 0.0 || self.y.size() < 0.0 || self.z.size() < 0.0
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        Vec3::new(
+            0.5 * (self.x.min + self.x.max),
+            0.5 * (self.y.min + self.y.max),
+            0.5 * (self.z.min + self.z.max),
+        )
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    /// Index of the axis along which the box is widest.
+    pub fn longest_axis(&self) -> usize {
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        if dx > dy && dx > dz {
+            0
+        } else if dy > dz {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Slab test, true if the ray passes through the box anywhere within `ray_t`.
+    pub fn hit(&self, ray: &Ray, mut ray_t: Interval) -> bool {
+        for n in 0..3 {
+            let slab = self.axis(n);
+            let inv_d = 1.0 / ray.direction[n];
+            let mut t0 = (slab.min - ray.origin[n]) * inv_d;
+            let mut t1 = (slab.max - ray.origin[n]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            ray_t.min = ray_t.min.max(t0);
+            ray_t.max = ray_t.max.min(t1);
+            if ray_t.max <= ray_t.min {
+                return false;
+            }
+        }
+        true
+    }
+}
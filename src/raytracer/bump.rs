@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use super::ray::HitRecord;
+use super::texture::Texture;
+use super::vec3::*;
+
+/// Detail added to a surface by changing its shading normal rather than its shape.
+pub enum BumpMap {
+    /// Normals encoded as colors in [0, 1], with x along `dpdu`, y along `dpdv` and z out of the surface
+    Normal(Arc<dyn Texture>),
+    /// Heights along the normal, the texture's luminance times `scale`
+    Height {
+        texture: Arc<dyn Texture>,
+        scale: f64,
+    },
+}
+
+impl BumpMap {
+    /// Shading normal on the outside of the surface, the side the geometric normal points to.
+    pub fn shading_normal(&self, hit_record: &HitRecord) -> Vec3 {
+        let normal = if hit_record.front_face {
+            hit_record.normal
+        } else {
+            -hit_record.normal
+        };
+        let perturbed = match self {
+            BumpMap::Normal(texture) => {
+                let c = texture.value(hit_record);
+                let local = vec3!(2.0 * c.r - 1.0, 2.0 * c.g - 1.0, 2.0 * c.b - 1.0);
+                let (tangent, bitangent) = hit_record.tangents(&normal);
+                local.x * tangent + local.y * bitangent + local.z * normal
+            }
+            BumpMap::Height { texture, scale } => {
+                displaced_normal(hit_record, &normal, texture.as_ref(), *scale)
+            }
+        };
+        if perturbed.near_zero() {
+            return normal;
+        }
+        perturbed.unit_vec()
+    }
+}
+
+/// Normal of the surface moved along `normal` by the height texture, from the change in height
+/// over about a pixel's footprint in each surface coordinate.
+fn displaced_normal(
+    hit_record: &HitRecord,
+    normal: &Vec3,
+    texture: &dyn Texture,
+    scale: f64,
+) -> Vec3 {
+    let height = |hit: &HitRecord| scale * texture.value(hit).luminance();
+    let step = |dx: f64, dy: f64| {
+        let step = 0.5 * (dx.abs() + dy.abs());
+        if step == 0.0 {
+            0.0005
+        } else {
+            step
+        }
+    };
+    let du = step(hit_record.duvdx.0, hit_record.duvdy.0);
+    let dv = step(hit_record.duvdx.1, hit_record.duvdy.1);
+
+    let mut shifted = *hit_record;
+    let displacement = height(hit_record);
+    shifted.point = hit_record.point + du * hit_record.dpdu;
+    shifted.u = hit_record.u + du;
+    let u_displacement = height(&shifted);
+    shifted.point = hit_record.point + dv * hit_record.dpdv;
+    shifted.u = hit_record.u;
+    shifted.v = hit_record.v + dv;
+    let v_displacement = height(&shifted);
+
+    let dpdu = hit_record.dpdu
+        + (u_displacement - displacement) / du * normal
+        + displacement * hit_record.dndu;
+    let dpdv = hit_record.dpdv
+        + (v_displacement - displacement) / dv * normal
+        + displacement * hit_record.dndv;
+    let bumped = dpdu.cross(&dpdv);
+    if bumped.dot(normal) < 0.0 {
+        -bumped
+    } else {
+        bumped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracer::color::Color;
+    use crate::raytracer::materials::{Lambertian, Material};
+    use crate::raytracer::ray::Ray;
+    use crate::raytracer::texture::SolidColor;
+
+    /// Height rising linearly with `u`.
+    struct Ramp;
+
+    impl Texture for Ramp {
+        fn value(&self, hit_record: &HitRecord) -> Color {
+            Color::new(hit_record.u, hit_record.u, hit_record.u)
+        }
+    }
+
+    /// Shading normal of the plane z = 0, mapped with `u` along x and `v` along y,
+    /// seen from above or from below.
+    fn shade(bump_map: &BumpMap, from_above: bool) -> Vec3 {
+        let material = Material::Lambertian(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let side = if from_above { 1.0 } else { -1.0 };
+        let ray = Ray::new(vec3!(0.0, 0.0, side), vec3!(0.0, 0.0, -side), 0.0);
+        let hit = HitRecord::new(
+            1.0,
+            &ray,
+            vec3!(0.3, 0.4, 0.0),
+            vec3!(0.0, 0.0, 1.0),
+            &material,
+        )
+        .with_uv(0.3, 0.4)
+        .with_uv_derivatives(vec3!(1.0, 0.0, 0.0), vec3!(0.0, 1.0, 0.0));
+        bump_map.shading_normal(&hit)
+    }
+
+    fn assert_near(a: &Vec3, b: &Vec3) {
+        assert!(
+            (a - b).length() < 1.0e-9,
+            "{:?} {:?}",
+            (a.x, a.y, a.z),
+            (b.x, b.y, b.z)
+        );
+    }
+
+    #[test]
+    fn height_ramp_tilts_the_normal_away_from_its_slope() {
+        let bump_map = BumpMap::Height {
+            texture: Arc::new(Ramp),
+            scale: 0.5,
+        };
+        // the surface (u, v, 0.5 u) has the normal (-0.5, 0, 1)
+        let expected = vec3!(-0.5, 0.0, 1.0).unit_vec();
+        assert_near(&shade(&bump_map, true), &expected);
+        assert_near(&shade(&bump_map, false), &expected);
+    }
+
+    #[test]
+    fn normal_maps_are_in_the_surface_frame() {
+        let flat = BumpMap::Normal(Arc::new(SolidColor::new(Color::new(0.5, 0.5, 1.0))));
+        assert_near(&shade(&flat, true), &vec3!(0.0, 0.0, 1.0));
+        let tilted = BumpMap::Normal(Arc::new(SolidColor::new(Color::new(1.0, 0.5, 1.0))));
+        assert_near(&shade(&tilted, true), &vec3!(1.0, 0.0, 1.0).unit_vec());
+    }
+}
@@ -0,0 +1,191 @@
+use super::aabb::{self, Aabb};
+use super::interval::Interval;
+use super::ray::*;
+
+/// Number of bins candidate splits are evaluated at along the widest axis.
+const BUCKETS: usize = 12;
+/// Lists at most this long stay unsplit if splitting is not estimated to be cheaper.
+const MAX_LEAF_SIZE: usize = 4;
+/// Cost of testing a node's box relative to intersecting one object.
+const TRAVERSAL_COST: f64 = 0.125;
+
+/// Bounding volume hierarchy node, children are either nodes or leaf object lists.
+pub struct BvhNode {
+    left: Box<dyn Hittable>,
+    right: Box<dyn Hittable>,
+    bbox: Aabb,
+    axis: usize,
+}
+
+impl BvhNode {
+    /// Builds a hierarchy over `objects` using the surface area heuristic and returns its root.
+    pub fn build(mut objects: Vec<Box<dyn Hittable>>) -> Box<dyn Hittable> {
+        if objects.len() == 1 {
+            return objects.pop().unwrap();
+        }
+
+        let bbox = objects.bounding_box();
+        let centroids = objects.iter().fold(aabb::EMPTY, |b, obj| {
+            let c = obj.bounding_box().centroid();
+            Aabb::enclosing(&b, &Aabb::from_points(c, c))
+        });
+        let axis = centroids.longest_axis();
+        let extent = *centroids.axis(axis);
+        if objects.is_empty() || extent.size() <= 0.0 {
+            // nothing to split, or all centroids coincide
+            return Box::new(objects);
+        }
+
+        let bucket_of = |obj: &dyn Hittable| {
+            let c = obj.bounding_box().centroid()[axis];
+            (((c - extent.min) / extent.size() * BUCKETS as f64) as usize).min(BUCKETS - 1)
+        };
+
+        let mut counts = [0usize; BUCKETS];
+        let mut bounds = [aabb::EMPTY; BUCKETS];
+        for obj in &objects {
+            let b = bucket_of(obj.as_ref());
+            counts[b] += 1;
+            bounds[b] = Aabb::enclosing(&bounds[b], &obj.bounding_box());
+        }
+
+        // cost of splitting after each bucket, relative to intersecting one object
+        let mut best_cost = f64::INFINITY;
+        let mut best_split = 0;
+        for split in 0..BUCKETS - 1 {
+            let (left_box, left_count) = Self::merge(&bounds[..=split], &counts[..=split]);
+            let (right_box, right_count) = Self::merge(&bounds[split + 1..], &counts[split + 1..]);
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+            let cost = TRAVERSAL_COST
+                + (left_box.surface_area() * left_count as f64
+                    + right_box.surface_area() * right_count as f64)
+                    / bbox.surface_area();
+            if cost < best_cost {
+                best_cost = cost;
+                best_split = split;
+            }
+        }
+
+        let leaf_cost = objects.len() as f64;
+        if !best_cost.is_finite() || (objects.len() <= MAX_LEAF_SIZE && leaf_cost <= best_cost) {
+            return Box::new(objects);
+        }
+
+        let (left, right): (Vec<_>, Vec<_>) = objects
+            .into_iter()
+            .partition(|obj| bucket_of(obj.as_ref()) <= best_split);
+
+        Box::new(Self {
+            left: Self::build(left),
+            right: Self::build(right),
+            bbox,
+            axis,
+        })
+    }
+
+    fn merge(bounds: &[Aabb], counts: &[usize]) -> (Aabb, usize) {
+        bounds
+            .iter()
+            .zip(counts)
+            .fold((aabb::EMPTY, 0), |(bbox, count), (b, c)| {
+                (Aabb::enclosing(&bbox, b), count + c)
+            })
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        if !self.bbox.hit(ray, ray_t) {
+            return None;
+        }
+
+        // visit the child nearer to the ray origin first to shrink the interval early
+        let (first, second) = if ray.direction[self.axis] < 0.0 {
+            (&self.right, &self.left)
+        } else {
+            (&self.left, &self.right)
+        };
+
+        let first_hit = first.hit(ray, ray_t);
+        let closest_so_far = first_hit.as_ref().map_or(ray_t.max, |hit| hit.t);
+        second
+            .hit(ray, Interval::new(ray_t.min, closest_so_far))
+            .or(first_hit)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::raytracer::color::Color;
+    use crate::raytracer::materials::{Lambertian, Material};
+    use crate::raytracer::sphere::Sphere;
+    use crate::raytracer::vec3::*;
+
+    fn random_spheres(rng: &mut SmallRng) -> Vec<Box<dyn Hittable>> {
+        let material = Arc::new(Material::Lambertian(Lambertian::new(Color::new(
+            0.5, 0.5, 0.5,
+        ))));
+        (0..500)
+            .map(|_| {
+                let center = vec3!(
+                    rng.gen_range(-20.0..20.0),
+                    rng.gen_range(-20.0..20.0),
+                    rng.gen_range(-20.0..20.0)
+                );
+                Box::new(Sphere::new(
+                    center,
+                    rng.gen_range(0.1..2.0),
+                    material.clone(),
+                )) as Box<dyn Hittable>
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bvh_hits_match_linear_list() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let list = random_spheres(&mut rng);
+        let bvh = BvhNode::build(random_spheres(&mut SmallRng::seed_from_u64(1)));
+
+        let mut hits = 0;
+        for _ in 0..10_000 {
+            let origin = 30.0 * Vec3::random_unit_vec(&mut rng);
+            let target = vec3!(
+                rng.gen_range(-20.0..20.0),
+                rng.gen_range(-20.0..20.0),
+                rng.gen_range(-20.0..20.0)
+            );
+            let ray = Ray::new(origin, target - origin, 0.0);
+            let ray_t = Interval::new(0.001, f64::INFINITY);
+
+            match (list.hit(&ray, ray_t), bvh.hit(&ray, ray_t)) {
+                (None, None) => {}
+                (Some(expected), Some(actual)) => {
+                    assert_eq!(expected.t, actual.t);
+                    assert_eq!(expected.normal.x, actual.normal.x);
+                    assert_eq!(expected.normal.y, actual.normal.y);
+                    assert_eq!(expected.normal.z, actual.normal.z);
+                    hits += 1;
+                }
+                (expected, actual) => panic!(
+                    "linear list hit: {}, bvh hit: {}",
+                    expected.is_some(),
+                    actual.is_some()
+                ),
+            }
+        }
+        assert!(hits > 1000);
+    }
+}
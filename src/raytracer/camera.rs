@@ -1,15 +1,68 @@
-use rand::rngs::ThreadRng;
+use std::f64::consts::PI;
+
+use rand::rngs::SmallRng;
 use rand::Rng;
 
 use super::color::*;
 use super::interval::Interval;
 use super::materials::Scatterable;
 use super::ray::*;
+use super::spectrum::{Spectral, Wavelengths};
 use super::vec3::*;
+use super::world::World;
+
+/// How directions around the camera map onto the image.
+#[derive(Clone, Copy)]
+pub enum Projection {
+    Perspective,
+    /// Parallel rays covering the area the perspective view shows at the focus distance
+    Orthographic,
+    /// Equidistant, `vfov` is the angle across the image height and corners beyond 360° stay black
+    Fisheye,
+    /// All directions, longitude across and latitude down the image regardless of `vfov`
+    Equirectangular,
+}
+
+/// Renders a view for each eye into one image.
+#[derive(Clone, Copy)]
+pub struct Stereo {
+    /// Distance between the eyes
+    pub eye_separation: f64,
+    /// Distance at which both eyes look at the same point
+    pub convergence: f64,
+    pub layout: StereoLayout,
+}
+
+#[derive(Clone, Copy)]
+pub enum StereoLayout {
+    /// Left eye on the left
+    SideBySide,
+    /// Left eye on top
+    TopBottom,
+}
+
+/// Position of one eye relative to the camera's center.
+struct Eye {
+    /// Distance to the right
+    offset: f64,
+    convergence: f64,
+}
+
+impl Eye {
+    /// Ray from the eye, offset along `right`, towards the point at the convergence distance
+    /// in the unit `direction` from `center`.
+    fn ray(&self, center: Vec3, right: Vec3, direction: Vec3, time: f64) -> Ray {
+        let offset = self.offset * right;
+        Ray::new(center + offset, self.convergence * direction - offset, time)
+    }
+}
 
 pub struct Camera {
     pub img_width: usize,
     pub img_height: usize,
+    projection: Projection,
+    /// Radians
+    vfov: f64,
     origin: Vec3,
     u: Vec3,
     v: Vec3,
@@ -17,11 +70,21 @@ pub struct Camera {
     pixel00: Vec3,
     du: Vec3,
     dv: Vec3,
+    stereo: Option<Stereo>,
+    /// Times rays are sent at are spread evenly over this interval
+    shutter_open: f64,
+    shutter_close: f64,
+    /// Distance to the plane in focus, which the viewport lies in
+    focus_distance: f64,
+    lens_radius: f64,
     sample_size: u32,
     max_depth: u32,
+    /// Paths carry light at sampled wavelengths rather than as RGB
+    spectral: Option<Spectral>,
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         img_width: usize,
         img_height: usize,
@@ -53,6 +116,8 @@ impl Camera {
         Self {
             img_width,
             img_height,
+            projection: Projection::Perspective,
+            vfov: vfov.to_radians(),
             origin: look_from,
             u,
             v,
@@ -60,75 +125,565 @@ impl Camera {
             pixel00,
             du,
             dv,
+            stereo: None,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            focus_distance: focal_length,
+            lens_radius: 0.0,
             sample_size,
             max_depth,
+            spectral: None,
         }
     }
 
-    pub fn ray(&self, x: usize, y: usize, rng: &mut ThreadRng) -> Ray {
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    /// Renders both eyes, each at the size given to `new`.
+    pub fn with_stereo(mut self, stereo: Stereo) -> Self {
+        self.stereo = Some(stereo);
+        self
+    }
+
+    /// Moving objects are blurred over the time the shutter is open.
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+
+    /// Renders with sampled wavelengths, which glass with a varying index of refraction disperses.
+    pub fn with_spectral(mut self, spectral: bool) -> Self {
+        self.spectral = spectral.then(Spectral::new);
+        self
+    }
+
+    /// Diameter of the lens, zero for a pinhole camera where everything is in focus.
+    /// Only perspective projections are blurred.
+    pub fn with_aperture(mut self, aperture: f64) -> Self {
+        self.lens_radius = aperture / 2.0;
+        self
+    }
+
+    /// Moves the plane in focus, which is at `look_at` unless set.
+    pub fn with_focus_distance(mut self, focus_distance: f64) -> Self {
+        let scale = focus_distance / self.focus_distance;
+        self.pixel00 = self.origin + scale * (self.pixel00 - self.origin);
+        self.du = scale * self.du;
+        self.dv = scale * self.dv;
+        self.focus_distance = focus_distance;
+        self
+    }
+
+    /// Focuses on the plane through `point` facing the camera.
+    pub fn with_focus_on(self, point: Vec3) -> Self {
+        let focus_distance = (self.origin - point).dot(&self.w);
+        self.with_focus_distance(focus_distance)
+    }
+
+    pub fn focus_distance(&self) -> f64 {
+        self.focus_distance
+    }
+
+    /// Size of the rendered image, which holds both eyes' views for stereo.
+    pub fn output_size(&self) -> (usize, usize) {
+        match self.stereo.map(|s| s.layout) {
+            None => (self.img_width, self.img_height),
+            Some(StereoLayout::SideBySide) => (2 * self.img_width, self.img_height),
+            Some(StereoLayout::TopBottom) => (self.img_width, 2 * self.img_height),
+        }
+    }
+
+    /// Ray through a random point of a pixel of the output image,
+    /// none where the projection covers no directions.
+    pub fn ray(&self, x: usize, y: usize, rng: &mut SmallRng) -> Option<Ray> {
+        let Some(stereo) = &self.stereo else {
+            return self.eye_ray(x, y, None, rng);
+        };
+        // left eye first
+        let (right, x, y) = match stereo.layout {
+            StereoLayout::SideBySide => (x >= self.img_width, x % self.img_width, y),
+            StereoLayout::TopBottom => (y >= self.img_height, x, y % self.img_height),
+        };
+        let offset = stereo.eye_separation / 2.0;
+        let eye = Eye {
+            offset: if right { offset } else { -offset },
+            convergence: stereo.convergence,
+        };
+        self.eye_ray(x, y, Some(eye), rng)
+    }
+
+    /// Ray of the camera's center, or of an eye when rendering stereo,
+    /// with differentials towards the next pixels over and down.
+    fn eye_ray(&self, x: usize, y: usize, eye: Option<Eye>, rng: &mut SmallRng) -> Option<Ray> {
         let pixel_center = self.pixel00 + self.dv * (y as f64) + self.du * (x as f64);
         let sample = pixel_center + self.pixel_sample_square(rng);
-        let direction = sample - self.origin;
-        Ray::new(self.origin, direction)
+        let time = self.shutter_time(rng);
+        let lens = match self.projection {
+            Projection::Perspective if self.lens_radius > 0.0 => self.lens_offset(rng),
+            _ => vec3!(0.0, 0.0, 0.0),
+        };
+        let eye = eye.as_ref();
+        let ray = self.project(&sample, &lens, eye, time)?;
+
+        // more samples per pixel each cover less of it
+        let scale = (1.0 / (self.sample_size as f64).sqrt()).max(0.125);
+        let offset = |step: Vec3| {
+            let neighbour = self.project(&(sample + step), &lens, eye, time)?;
+            Some((
+                ray.origin + scale * (neighbour.origin - ray.origin),
+                ray.direction + scale * (neighbour.direction - ray.direction),
+            ))
+        };
+        let differential = match (offset(self.du), offset(self.dv)) {
+            (Some((rx_origin, rx_direction)), Some((ry_origin, ry_direction))) => {
+                Some(Differential {
+                    rx_origin,
+                    rx_direction,
+                    ry_origin,
+                    ry_direction,
+                })
+            }
+            _ => None,
+        };
+        Some(ray.with_differential(differential))
+    }
+
+    /// Ray through a point on the viewport, leaving the lens at `lens` from its center.
+    fn project(&self, sample: &Vec3, lens: &Vec3, eye: Option<&Eye>, time: f64) -> Option<Ray> {
+        match self.projection {
+            Projection::Perspective => {
+                let (origin, target) = match eye {
+                    None => (self.origin, *sample),
+                    // off-axis, each eye's viewport is shifted so both line up at the convergence plane
+                    Some(eye) => (
+                        self.origin + eye.offset * self.u,
+                        sample
+                            + eye.offset * (1.0 - self.focus_distance / eye.convergence) * self.u,
+                    ),
+                };
+                let origin = origin + lens;
+                Some(Ray::new(origin, target - origin, time))
+            }
+            Projection::Orthographic => {
+                let origin = sample + self.focus_distance * self.w;
+                Some(match eye {
+                    None => Ray::new(origin, -self.w, time),
+                    Some(eye) => Ray::new(
+                        origin + eye.offset * self.u,
+                        -eye.convergence * self.w - eye.offset * self.u,
+                        time,
+                    ),
+                })
+            }
+            Projection::Fisheye => {
+                let (sx, sy) = self.viewport_position(sample);
+                let sx = sx * self.img_width as f64 / self.img_height as f64;
+                let r = (sx * sx + sy * sy).sqrt();
+                let theta = r * self.vfov / 2.0;
+                if theta > PI {
+                    return None;
+                }
+                let side = if r > 0.0 {
+                    (sx / r) * self.u + (sy / r) * self.v
+                } else {
+                    self.u
+                };
+                let direction = theta.sin() * side - theta.cos() * self.w;
+                Some(match eye {
+                    None => Ray::new(self.origin, direction, time),
+                    Some(eye) => eye.ray(self.origin, self.u, direction, time),
+                })
+            }
+            Projection::Equirectangular => {
+                let (sx, sy) = self.viewport_position(sample);
+                let phi = sx * PI;
+                let theta = (1.0 - sy) / 2.0 * PI;
+                let direction =
+                    theta.sin() * (phi.sin() * self.u - phi.cos() * self.w) + theta.cos() * self.v;
+                Some(match eye {
+                    None => Ray::new(self.origin, direction, time),
+                    // omni-directional stereo, the eyes turn with the view around the up axis
+                    Some(eye) => {
+                        let right = phi.cos() * self.u + phi.sin() * self.w;
+                        eye.ray(self.origin, right, direction, time)
+                    }
+                })
+            }
+        }
     }
 
-    pub fn pixel_sample_square(&self, rng: &mut ThreadRng) -> Vec3 {
+    /// Position of a point on the viewport relative to its center, in halves of its width and height.
+    fn viewport_position(&self, point: &Vec3) -> (f64, f64) {
+        let offset = point - (self.origin - self.focus_distance * self.w);
+        let half_width = self.du.length() * self.img_width as f64 / 2.0;
+        let half_height = self.dv.length() * self.img_height as f64 / 2.0;
+        (
+            offset.dot(&self.u) / half_width,
+            offset.dot(&self.v) / half_height,
+        )
+    }
+
+    fn shutter_time(&self, rng: &mut SmallRng) -> f64 {
+        if self.shutter_close > self.shutter_open {
+            rng.gen_range(self.shutter_open..self.shutter_close)
+        } else {
+            self.shutter_open
+        }
+    }
+
+    fn lens_offset(&self, rng: &mut SmallRng) -> Vec3 {
+        let p = Vec3::random_in_unit_disk(rng);
+        self.lens_radius * (p.x * self.u + p.y * self.v)
+    }
+
+    pub fn pixel_sample_square(&self, rng: &mut SmallRng) -> Vec3 {
         let px = -0.5 + rng.gen::<f64>();
         let py = -0.5 + rng.gen::<f64>();
-        return (px * self.du) + (py * self.dv);
+        (px * self.du) + (py * self.dv)
     }
 
-    pub fn render(
-        &self,
-        y: usize,
-        band: &mut [u8],
-        world: &Vec<Box<dyn Hittable>>,
-        rng: &mut ThreadRng,
-    ) {
-        for x in 0..self.img_width {
+    pub fn render(&self, y: usize, band: &mut [Color], world: &World, rng: &mut SmallRng) {
+        for (x, pixel) in band.iter_mut().enumerate() {
             let mut color = Color::new(0.0, 0.0, 0.0);
             for _ in 0..self.sample_size {
-                let ray = self.ray(x, y, rng);
-                color += Self::ray_color(&ray, world, self.max_depth, rng);
+                let Some(ray) = self.ray(x, y, rng) else {
+                    continue;
+                };
+                color += match &self.spectral {
+                    Some(spectral) => {
+                        let wavelengths = spectral.sample(rng.gen());
+                        let ray = ray.with_wavelength(Some(wavelengths.hero()));
+                        let values = self.ray_color(ray, world, Some(&wavelengths), rng);
+                        spectral.to_rgb(&wavelengths, &values)
+                    }
+                    None => self.ray_color(ray, world, None, rng),
+                };
             }
-            let c = (color / self.sample_size).linear_to_gamma();
-            Self::write_pixel(band, x, c);
+            *pixel = color / self.sample_size;
         }
     }
 
+    /// Follows a path of up to `max_depth` rays. Besides finding lights and the environment by scattering,
+    /// every bounce that is not mirror-like samples them directly and the two are combined with multiple
+    /// importance sampling.
+    /// With `wavelengths`, colors are values at them instead.
     fn ray_color(
-        ray: &Ray,
-        world: &Vec<Box<dyn Hittable>>,
-        depth: u32,
-        rng: &mut ThreadRng,
+        &self,
+        mut ray: Ray,
+        world: &World,
+        wavelengths: Option<&Wavelengths>,
+        rng: &mut SmallRng,
     ) -> Color {
-        if depth <= 0 {
-            return Color::new(0.0, 0.0, 0.0);
+        let mut color = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        // density the last direction was scattered with, zero for the camera and mirror-like bounces
+        let mut scatter_pdf = 0.0;
+        let mut hero_only = false;
+
+        for _ in 0..self.max_depth {
+            let Some(mut rec) = world.objects.hit(&ray, Interval::new(0.001, f64::INFINITY)) else {
+                let weight = if scatter_pdf > 0.0 {
+                    power_heuristic(scatter_pdf, world.environment.pdf(&ray.direction))
+                } else {
+                    1.0
+                };
+                let light = spectrum(wavelengths, world.environment.color(&ray.direction));
+                color += weight * (throughput * light);
+                break;
+            };
+
+            if rec.material.is_emissive() {
+                let weight = if scatter_pdf > 0.0 {
+                    power_heuristic(
+                        scatter_pdf,
+                        world.light_pdf(&ray.origin, &ray.direction, ray.time),
+                    )
+                } else {
+                    1.0
+                };
+                color += weight * (throughput * spectrum(wavelengths, rec.material.emitted(&rec)));
+            }
+            rec.compute_differentials(&ray);
+            if let Some(normal) = rec.material.shading_normal(&rec) {
+                rec = rec.with_shading_normal(normal);
+            }
+            // each wavelength would go its own way, only the hero's is followed
+            if wavelengths.is_some() && !hero_only && rec.material.is_dispersive() {
+                throughput = throughput * Wavelengths::hero_only();
+                hero_only = true;
+            }
+            // lights are sampled whether or not scattering goes on to find a direction
+            let specular = rec.material.is_specular();
+            if !specular {
+                color += throughput * sample_lights(&ray, &rec, world, wavelengths, rng);
+                color += throughput * sample_environment(&ray, &rec, world, wavelengths, rng);
+            }
+            let Some((scattered, attenuation)) = rec.material.scatter(&ray, &rec, rng) else {
+                break;
+            };
+            if !rec.is_consistent(&scattered.direction) {
+                break;
+            }
+            scatter_pdf = if specular {
+                0.0
+            } else {
+                rec.material.pdf(&ray, &rec, &scattered.direction)
+            };
+            if !specular && scatter_pdf == 0.0 {
+                // lights found along it would count again, on top of those just sampled
+                break;
+            }
+            throughput = throughput * spectrum(wavelengths, attenuation);
+            ray = scattered.with_wavelength(ray.wavelength);
         }
+        color
+    }
+}
 
-        let hit = world.hit(ray, Interval::new(0.001, f64::INFINITY));
-        match hit {
-            Some(rec) => {
-                let scattered = rec.material.scatter(ray, &rec, rng);
-                match scattered {
-                    Some((sc_ray, albedo)) => {
-                        albedo * Self::ray_color(&sc_ray, world, depth - 1, rng)
-                    }
-                    None => return Color::new(0.0, 0.0, 0.0),
+/// Light arriving at the hit point from a direction towards a random light,
+/// weighted against finding the same direction by scattering.
+fn sample_lights(
+    ray: &Ray,
+    rec: &HitRecord,
+    world: &World,
+    wavelengths: Option<&Wavelengths>,
+    rng: &mut SmallRng,
+) -> Color {
+    let black = Color::new(0.0, 0.0, 0.0);
+    if world.lights.is_empty() {
+        return black;
+    }
+    let light = &world.lights[rng.gen_range(0..world.lights.len())];
+    let direction = light.random(&rec.point, ray.time, rng);
+    let scatter_pdf = rec.material.pdf(ray, rec, &direction);
+    if scatter_pdf == 0.0 || !rec.is_consistent(&direction) {
+        return black;
+    }
+    // whatever is seen first counts, as it would when scattering in the same direction
+    let shadow_ray = Ray::new(rec.point, direction, ray.time);
+    let Some(hit) = world
+        .objects
+        .hit(&shadow_ray, Interval::new(0.001, f64::INFINITY))
+    else {
+        return black;
+    };
+    if !hit.material.is_emissive() {
+        return black;
+    }
+    let light_pdf = world.light_pdf(&rec.point, &direction, ray.time);
+    if light_pdf == 0.0 {
+        return black;
+    }
+    let weight = power_heuristic(light_pdf, scatter_pdf) / light_pdf;
+    let eval = spectrum(wavelengths, rec.material.eval(ray, rec, &direction));
+    weight * (eval * spectrum(wavelengths, hit.material.emitted(&hit)))
+}
+
+/// Light arriving at the hit point from a direction picked by the environment,
+/// weighted against finding the same direction by scattering.
+fn sample_environment(
+    ray: &Ray,
+    rec: &HitRecord,
+    world: &World,
+    wavelengths: Option<&Wavelengths>,
+    rng: &mut SmallRng,
+) -> Color {
+    let black = Color::new(0.0, 0.0, 0.0);
+    let Some((direction, light, light_pdf)) = world.environment.sample(rng) else {
+        return black;
+    };
+    let scatter_pdf = rec.material.pdf(ray, rec, &direction);
+    if scatter_pdf == 0.0 || !rec.is_consistent(&direction) {
+        return black;
+    }
+    let shadow_ray = Ray::new(rec.point, direction, ray.time);
+    if world
+        .objects
+        .hit(&shadow_ray, Interval::new(0.001, f64::INFINITY))
+        .is_some()
+    {
+        return black;
+    }
+    let weight = power_heuristic(light_pdf, scatter_pdf) / light_pdf;
+    let eval = spectrum(wavelengths, rec.material.eval(ray, rec, &direction));
+    weight * (eval * spectrum(wavelengths, light))
+}
+
+/// A linear RGB color as its values at the path's wavelengths when rendering spectrally.
+fn spectrum(wavelengths: Option<&Wavelengths>, color: Color) -> Color {
+    match wavelengths {
+        Some(wavelengths) => wavelengths.uplift(&color),
+        None => color,
+    }
+}
+
+/// Weight of a sample from the strategy with density `pdf` against one with density `other_pdf`.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    a / (a + b)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::raytracer::environment::{Environment, Sky};
+    use crate::raytracer::input::Image;
+    use crate::raytracer::instance::Instance;
+    use crate::raytracer::materials::{DiffuseLight, Lambertian, Material, Metal, Principled};
+    use crate::raytracer::ray::Hittable;
+    use crate::raytracer::sphere::Sphere;
+    use crate::raytracer::texture::SolidColor;
+    use crate::raytracer::transform::{Mat4, Transform};
+    use crate::raytracer::world::Group;
+
+    /// Sky of a map that is white everywhere, which is sampled unlike a constant one.
+    fn white_map() -> Sky {
+        Sky::Map(Image {
+            width: 16,
+            height: 8,
+            pixels: vec![Color::new(1.0, 1.0, 1.0); 16 * 8],
+        })
+    }
+
+    /// Mean luminance of a sphere filling the view, lit by nothing but the sky.
+    fn furnace(material: Material, sky: Sky) -> f64 {
+        let sphere = Sphere::new(vec3!(0.0, 0.0, 0.0), 1.0, Arc::new(material));
+        let world = World::new(
+            vec![Box::new(sphere) as Box<dyn Hittable>],
+            Environment::new(sky),
+        );
+        render_mean(&world, 500)
+    }
+
+    fn render_mean(world: &World, samples: u32) -> f64 {
+        let camera = Camera::new(
+            8,
+            8,
+            vec3!(0.0, 0.0, 4.0),
+            vec3!(0.0, 0.0, 0.0),
+            vec3!(0.0, 1.0, 0.0),
+            10.0,
+            samples,
+            8,
+        );
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut sum = 0.0;
+        for y in 0..camera.img_height {
+            for x in 0..camera.img_width {
+                for _ in 0..samples {
+                    let ray = camera.ray(x, y, &mut rng).unwrap();
+                    sum += camera.ray_color(ray, world, None, &mut rng).luminance();
                 }
             }
-            None => {
-                // sky
-                let a = (ray.direction.unit_vec().y + 1.0) * 0.5;
-                return (1.0 - a) * Color::new(1.0, 1.0, 1.0) + a * Color::new(0.5, 0.7, 1.0);
-            }
         }
+        sum / (camera.img_width * camera.img_height) as f64 / samples as f64
+    }
+
+    #[test]
+    fn white_lambertian_furnace_is_white() {
+        let white = || Material::Lambertian(Lambertian::new(Color::new(1.0, 1.0, 1.0)));
+        for sky in [Sky::Constant(Color::new(1.0, 1.0, 1.0)), white_map()] {
+            let mean = furnace(white(), sky);
+            assert!((mean - 1.0).abs() < 1.0e-2, "{}", mean);
+        }
+    }
+
+    #[test]
+    fn rough_metal_furnace_is_the_same_under_a_sampled_map() {
+        // scattering below the surface fails most often for rough metals
+        for roughness in [0.5, 0.7, 1.0] {
+            let metal = || Material::Metal(Metal::new(Color::new(1.0, 1.0, 1.0), roughness));
+            let constant = furnace(metal(), Sky::Constant(Color::new(1.0, 1.0, 1.0)));
+            let map = furnace(metal(), white_map());
+            assert!((constant - map).abs() < 1.0e-2, "{} {}", constant, map);
+        }
+    }
+
+    #[test]
+    fn rough_principled_metal_furnace_is_the_same_under_a_sampled_map() {
+        let metal = || {
+            let white = Arc::new(SolidColor::new(Color::new(1.0, 1.0, 1.0)));
+            Material::Principled(
+                Principled::textured(white)
+                    .with_metallic(1.0)
+                    .with_roughness(0.7),
+            )
+        };
+        let constant = furnace(metal(), Sky::Constant(Color::new(1.0, 1.0, 1.0)));
+        let map = furnace(metal(), white_map());
+        assert!((constant - map).abs() < 1.0e-2, "{} {}", constant, map);
+    }
+
+    /// Renders the world sampling its lights, and finding them by scattering alone.
+    fn assert_light_sampling_agrees(world: impl Fn() -> World) {
+        // scattering alone finds the light far less often, so takes more samples
+        let sampled = render_mean(&world(), 500);
+        let mut scattering_only = world();
+        scattering_only.lights.clear();
+        let found = render_mean(&scattering_only, 4000);
+        assert!(
+            (sampled - found).abs() < 0.02 * found,
+            "{} {}",
+            sampled,
+            found
+        );
+    }
+
+    fn rough_metal() -> Arc<Material> {
+        Arc::new(Material::Metal(Metal::new(Color::new(1.0, 1.0, 1.0), 0.7)))
+    }
+
+    fn light() -> Arc<Material> {
+        Arc::new(Material::DiffuseLight(DiffuseLight::new(Color::new(
+            4.0, 4.0, 4.0,
+        ))))
+    }
+
+    fn black_sky() -> Environment {
+        Environment::new(Sky::Constant(Color::new(0.0, 0.0, 0.0)))
+    }
+
+    #[test]
+    fn sampling_lights_agrees_with_finding_them_by_scattering() {
+        assert_light_sampling_agrees(|| {
+            World::new(
+                vec![
+                    Box::new(Sphere::new(vec3!(0.0, 0.0, 0.0), 1.0, rough_metal())),
+                    Box::new(Sphere::new(vec3!(1.0, 2.5, 2.0), 1.0, light())),
+                ],
+                black_sky(),
+            )
+        });
     }
 
-    fn write_pixel(band: &mut [u8], x: usize, color: Color) {
-        let p = x * 3;
-        band[p] = (256.0 * color.r) as u8;
-        band[p + 1] = (256.0 * color.g) as u8;
-        band[p + 2] = (256.0 * color.b) as u8;
+    #[test]
+    fn emissive_overrides_make_instances_lights() {
+        let world = || {
+            // shared geometry that does not emit on its own
+            let gray = Arc::new(Material::Lambertian(Lambertian::new(Color::new(
+                0.5, 0.5, 0.5,
+            ))));
+            let group: Arc<dyn Hittable> = Arc::new(Group::new(vec![
+                Box::new(Sphere::new(vec3!(0.0, 0.0, 0.0), 0.6, gray.clone())),
+                Box::new(Sphere::new(vec3!(1.5, 0.0, 0.0), 0.8, gray)),
+            ]));
+            let placement = Transform::new(Mat4::translation(vec3!(0.0, 2.5, 2.0))).unwrap();
+            World::new(
+                vec![
+                    Box::new(Sphere::new(vec3!(0.0, 0.0, 0.0), 1.0, rough_metal())),
+                    Box::new(Instance::new(group, placement).with_material(light())),
+                ],
+                black_sky(),
+            )
+        };
+        assert_eq!(world().lights.len(), 1);
+        assert_light_sampling_agrees(world);
     }
 }
@@ -23,6 +23,15 @@ impl Color {
     pub fn linear_to_gamma(&self) -> Self {
         Self::new(self.r.sqrt(), self.g.sqrt(), self.b.sqrt())
     }
+
+    pub fn gamma_to_linear(&self) -> Self {
+        Self::new(self.r * self.r, self.g * self.g, self.b * self.b)
+    }
+
+    /// Perceived brightness with Rec. 709 weights.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
 }
 
 impl_op_ex!(+|a: &Color, b: &Color| -> Color { Color::new(a.r + b.r, a.g + b.g, a.b + b.b) });
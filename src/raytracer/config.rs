@@ -1,37 +1,83 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
 use super::camera::Camera;
-use super::ray::Hittable;
-use super::vec3::*;
+use super::world::World;
 
 pub struct Config {
+    /// Title of the viewer window
+    #[cfg(feature = "viewer")]
     pub title: &'static str,
-    pub width: usize,
-    pub height: usize,
     pub camera: Camera,
-    pub world: Vec<Box<dyn Hittable>>,
+    pub world: World,
+    /// Where to save the rendered image, if anywhere
+    pub output: Option<PathBuf>,
+    /// Whether to show the rendered image in a window
+    pub viewer: bool,
+    /// Seed for the per-row random number generators
+    pub seed: u64,
 }
 
 impl Config {
     pub fn new(
-        title: &'static str,
-        width: usize,
-        height: usize,
-        world: Vec<Box<dyn Hittable>>,
+        #[cfg_attr(not(feature = "viewer"), allow(unused_variables))] title: &'static str,
+        camera: Camera,
+        world: World,
     ) -> Self {
         Self {
+            #[cfg(feature = "viewer")]
             title,
-            width,
-            height,
-            camera: Camera::new(
-                width,
-                height,
-                vec3!(13.0, 2.0, 3.0),
-                vec3!(0.0, 0.0, 0.0),
-                vec3!(0.0, 1.0, 0.0),
-                20.0,
-                500,
-                50,
-            ),
+            camera,
             world,
+            output: None,
+            viewer: true,
+            seed: 0,
+        }
+    }
+}
+
+/// Image and sampling parameters, each of which may come from the command line or a scene file.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RenderSettings {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub samples: Option<u32>,
+    pub max_depth: Option<u32>,
+    /// Sample wavelengths instead of rendering in RGB
+    pub spectral: Option<bool>,
+}
+
+impl RenderSettings {
+    /// Fills every unset field from `other`.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            width: self.width.or(other.width),
+            height: self.height.or(other.height),
+            samples: self.samples.or(other.samples),
+            max_depth: self.max_depth.or(other.max_depth),
+            spectral: self.spectral.or(other.spectral),
         }
     }
+
+    pub fn width(&self) -> usize {
+        self.width.unwrap_or(1200) as usize
+    }
+
+    pub fn height(&self) -> usize {
+        self.height.unwrap_or(675) as usize
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples.unwrap_or(500)
+    }
+
+    pub fn max_depth(&self) -> u32 {
+        self.max_depth.unwrap_or(50)
+    }
+
+    pub fn spectral(&self) -> bool {
+        self.spectral.unwrap_or(false)
+    }
 }
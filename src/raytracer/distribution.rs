@@ -0,0 +1,138 @@
+/// Piecewise-constant density over [0, 1) proportional to `func`.
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    /// `func` must not be empty or negative, all zeros gives a uniform density.
+    pub fn new(func: Vec<f64>) -> Self {
+        let n = func.len() as f64;
+        let mut cdf = Vec::with_capacity(func.len() + 1);
+        cdf.push(0.0);
+        for f in &func {
+            cdf.push(cdf.last().unwrap() + f / n);
+        }
+        let integral = *cdf.last().unwrap();
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = if integral > 0.0 {
+                *c / integral
+            } else {
+                i as f64 / n
+            };
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Maps a uniform `u` in [0, 1) to `x`, returning it with its density and segment.
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        let segment = (self.cdf.partition_point(|&c| c <= u) - 1).min(self.func.len() - 1);
+        let width = self.cdf[segment + 1] - self.cdf[segment];
+        let offset = if width > 0.0 {
+            (u - self.cdf[segment]) / width
+        } else {
+            0.0
+        };
+        let x = (segment as f64 + offset) / self.func.len() as f64;
+        (x, self.density(segment), segment)
+    }
+
+    pub fn pdf(&self, x: f64) -> f64 {
+        let segment = ((x * self.func.len() as f64) as usize).min(self.func.len() - 1);
+        self.density(segment)
+    }
+
+    fn density(&self, segment: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.func[segment] / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Piecewise-constant density over [0, 1)², e.g. proportional to the pixels of an image.
+/// Rows are picked first, then a column within the row.
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// `func` is indexed `[row][column]`, all rows need the same length.
+    pub fn new(func: Vec<Vec<f64>>) -> Self {
+        let rows: Vec<_> = func.into_iter().map(Distribution1D::new).collect();
+        let marginal = Distribution1D::new(rows.iter().map(|row| row.integral()).collect());
+        Self { rows, marginal }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.marginal.integral()
+    }
+
+    /// Maps uniform `(u1, u2)` to `(u, v)` with `v` selecting the row, returning them with their density.
+    pub fn sample(&self, u1: f64, u2: f64) -> ((f64, f64), f64) {
+        let (v, pdf_v, row) = self.marginal.sample(u2);
+        let (u, pdf_u, _) = self.rows[row].sample(u1);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let row = ((v * self.rows.len() as f64) as usize).min(self.rows.len() - 1);
+        self.rows[row].pdf(u) * self.marginal.pdf(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distribution_2d_samples_match_its_pdf() {
+        let func = vec![
+            vec![1.0, 0.0, 3.0, 2.0],
+            vec![0.0, 0.0, 0.0, 0.0],
+            vec![4.0, 1.0, 1.0, 8.0],
+        ];
+        let total: f64 = func.iter().flatten().sum();
+        let distribution = Distribution2D::new(func.clone());
+
+        // a stratified grid of samples lands in each cell in proportion to its value
+        let n = 400;
+        let mut counts = [[0usize; 4]; 3];
+        for i in 0..n {
+            for j in 0..n {
+                let (u1, u2) = ((i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64);
+                let ((u, v), pdf) = distribution.sample(u1, u2);
+                assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+                assert!((pdf - distribution.pdf(u, v)).abs() < 1.0e-9);
+                counts[(v * 3.0) as usize][(u * 4.0) as usize] += 1;
+            }
+        }
+        for (row, counts) in func.iter().zip(&counts) {
+            for (f, &count) in row.iter().zip(counts) {
+                let expected = f / total;
+                assert!((count as f64 / (n * n) as f64 - expected).abs() < 1.0e-2);
+            }
+        }
+
+        // the density integrates to one over the unit square
+        let integral: f64 = (0..3)
+            .flat_map(|row| (0..4).map(move |column| (row, column)))
+            .map(|(row, column)| {
+                let (u, v) = ((column as f64 + 0.5) / 4.0, (row as f64 + 0.5) / 3.0);
+                distribution.pdf(u, v) / 12.0
+            })
+            .sum();
+        assert!((integral - 1.0).abs() < 1.0e-9);
+    }
+}
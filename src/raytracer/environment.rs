@@ -0,0 +1,168 @@
+use std::f64::consts::PI;
+
+use rand::rngs::SmallRng;
+use rand::Rng;
+
+use super::color::Color;
+use super::distribution::Distribution2D;
+use super::input::Image;
+use super::vec3::*;
+
+/// What the environment looks like before rotation and intensity are applied.
+pub enum Sky {
+    Constant(Color),
+    /// Blend from straight down to straight up
+    Gradient {
+        bottom: Color,
+        top: Color,
+    },
+    /// Equirectangular image, the top row looking straight up
+    Map(Image),
+}
+
+/// Light arriving from infinitely far away in directions that hit nothing.
+pub struct Environment {
+    sky: Sky,
+    /// Radians around the up axis
+    rotation: f64,
+    intensity: f64,
+    /// Over the image by luminance, for sampling directions towards the bright parts
+    distribution: Option<Distribution2D>,
+}
+
+impl Default for Environment {
+    /// The white to blue sky of Ray Tracing in One Weekend.
+    fn default() -> Self {
+        Self::new(Sky::Gradient {
+            bottom: Color::new(1.0, 1.0, 1.0),
+            top: Color::new(0.5, 0.7, 1.0),
+        })
+    }
+}
+
+impl Environment {
+    pub fn new(sky: Sky) -> Self {
+        let distribution = match &sky {
+            Sky::Map(image) => Some(luminance_distribution(image)),
+            _ => None,
+        }
+        .filter(|d| d.integral() > 0.0);
+        Self {
+            sky,
+            rotation: 0.0,
+            intensity: 1.0,
+            distribution,
+        }
+    }
+
+    /// Turns the environment around the up axis.
+    pub fn with_rotation(mut self, degrees: f64) -> Self {
+        self.rotation = degrees.to_radians();
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn color(&self, direction: &Vec3) -> Color {
+        let d = rotate_y(&direction.unit_vec(), -self.rotation);
+        let color = match &self.sky {
+            Sky::Constant(color) => *color,
+            Sky::Gradient { bottom, top } => {
+                let a = (d.y + 1.0) * 0.5;
+                (1.0 - a) * bottom + a * top
+            }
+            Sky::Map(image) => {
+                let (u, v) = direction_to_uv(&d);
+                bilinear(image, u, v)
+            }
+        };
+        self.intensity * color
+    }
+
+    /// Picks a direction towards the environment with probability proportional to its brightness.
+    /// Returns the direction, the light arriving from it and its density in solid angle,
+    /// or nothing for skies smooth enough to be found by scattering alone.
+    pub fn sample(&self, rng: &mut SmallRng) -> Option<(Vec3, Color, f64)> {
+        let distribution = self.distribution.as_ref()?;
+        let ((u, v), pdf) = distribution.sample(rng.gen(), rng.gen());
+        let sin_theta = (v * PI).sin();
+        if pdf == 0.0 || sin_theta == 0.0 {
+            return None;
+        }
+        let direction = rotate_y(&uv_to_direction(u, v), self.rotation);
+        let color = self.color(&direction);
+        Some((direction, color, pdf / (2.0 * PI * PI * sin_theta)))
+    }
+
+    /// Density of `sample` returning `direction`, in solid angle.
+    pub fn pdf(&self, direction: &Vec3) -> f64 {
+        let Some(distribution) = &self.distribution else {
+            return 0.0;
+        };
+        let d = rotate_y(&direction.unit_vec(), -self.rotation);
+        let (u, v) = direction_to_uv(&d);
+        let sin_theta = (v * PI).sin();
+        if sin_theta == 0.0 {
+            return 0.0;
+        }
+        distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+/// Pixel luminance, weighted by the solid angle each row of the map covers.
+fn luminance_distribution(image: &Image) -> Distribution2D {
+    Distribution2D::new(
+        (0..image.height)
+            .map(|y| {
+                let sin_theta = ((y as f64 + 0.5) / image.height as f64 * PI).sin();
+                (0..image.width)
+                    .map(|x| image.pixel(x, y).luminance().max(0.0) * sin_theta)
+                    .collect()
+            })
+            .collect(),
+    )
+}
+
+fn rotate_y(d: &Vec3, angle: f64) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    vec3!(cos * d.x + sin * d.z, d.y, -sin * d.x + cos * d.z)
+}
+
+/// Equirectangular coordinates in [0, 1] of a unit direction, `v` from straight up.
+pub fn direction_to_uv(d: &Vec3) -> (f64, f64) {
+    let u = 0.5 + d.x.atan2(-d.z) / (2.0 * PI);
+    let v = d.y.clamp(-1.0, 1.0).acos() / PI;
+    (u, v)
+}
+
+/// Unit direction of equirectangular coordinates, inverse of `direction_to_uv`.
+pub fn uv_to_direction(u: f64, v: f64) -> Vec3 {
+    let phi = (u - 0.5) * 2.0 * PI;
+    let theta = v * PI;
+    vec3!(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos()
+    )
+}
+
+/// Wraps around horizontally and clamps at the poles.
+fn bilinear(image: &Image, u: f64, v: f64) -> Color {
+    let x = u * image.width as f64 - 0.5;
+    let y = (v * image.height as f64 - 0.5).clamp(0.0, (image.height - 1) as f64);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+
+    let w = image.width as i64;
+    let x0 = (x0 as i64).rem_euclid(w) as usize;
+    let x1 = (x0 + 1) % image.width;
+    let y0 = y0 as usize;
+    let y1 = (y0 + 1).min(image.height - 1);
+
+    let top = (1.0 - fx) * image.pixel(x0, y0) + fx * image.pixel(x1, y0);
+    let bottom = (1.0 - fx) * image.pixel(x0, y1) + fx * image.pixel(x1, y1);
+    (1.0 - fy) * top + fy * bottom
+}
@@ -0,0 +1,51 @@
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+use rayon::prelude::*;
+
+use super::camera::Camera;
+use super::color::Color;
+use super::world::World;
+
+/// In-memory image of averaged linear radiance, independent of any window or texture.
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::new(0.0, 0.0, 0.0); width * height],
+        }
+    }
+
+    /// Every row gets its own generator derived from `seed`, so the image does not
+    /// depend on how rows are spread over threads.
+    pub fn render(&mut self, camera: &Camera, world: &World, seed: u64) {
+        let bands: Vec<(usize, &mut [Color])> =
+            self.pixels.chunks_mut(self.width).enumerate().collect();
+
+        bands.into_par_iter().for_each(|(y, band)| {
+            let mut rng = SmallRng::seed_from_u64(seed.wrapping_add(y as u64));
+            camera.render(y, band, world, &mut rng)
+        });
+    }
+
+    /// Gamma corrected RGB24 bytes, row by row from the top.
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|c| {
+                let c = c.linear_to_gamma();
+                [
+                    (256.0 * c.r) as u8,
+                    (256.0 * c.g) as u8,
+                    (256.0 * c.b) as u8,
+                ]
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,373 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+use super::color::Color;
+
+/// Image of linear colors, row by row from the top.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Loads an image in the format given by the file extension.
+/// 8-bit formats are taken to be gamma encoded the same way our output is.
+pub fn load(path: &Path) -> Result<Image, String> {
+    load_image(path, true)
+}
+
+/// Loads an image of plain values such as normals or heights, which are not gamma encoded in any format.
+pub fn load_data(path: &Path) -> Result<Image, String> {
+    load_image(path, false)
+}
+
+fn load_image(path: &Path, gamma_encoded: bool) -> Result<Image, String> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+
+    let image = match extension.as_deref() {
+        Some("hdr") => open(path).and_then(read_hdr),
+        Some("pfm") => open(path).and_then(read_pfm),
+        Some("png") => open(path).and_then(|reader| read_png(reader, gamma_encoded)),
+        Some("exr") => read_exr(path),
+        _ => {
+            return Err(format!(
+                "{}: unsupported image format, expected .hdr, .exr, .pfm or .png",
+                path.display()
+            ))
+        }
+    };
+    // textures and skies sample images without checking they have any pixels
+    let image = image.and_then(|image| {
+        if image.pixels.is_empty() {
+            Err(invalid("empty image"))
+        } else {
+            Ok(image)
+        }
+    });
+    image.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn open(path: &Path) -> io::Result<BufReader<File>> {
+    File::open(path).map(BufReader::new)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Number of pixels in an image of the size a header gives, which has to be at least one.
+fn pixel_count(width: usize, height: usize) -> io::Result<usize> {
+    if width == 0 || height == 0 {
+        return Err(invalid("empty image"));
+    }
+    width
+        .checked_mul(height)
+        .ok_or_else(|| invalid("image too large"))
+}
+
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
+
+/// Radiance RGBE, flat or with run length encoded scanlines.
+pub fn read_hdr(mut reader: impl BufRead) -> io::Result<Image> {
+    if !read_line(&mut reader)?.starts_with("#?") {
+        return Err(invalid("not a Radiance HDR file"));
+    }
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+            return Err(invalid("only RGBE pixels are supported"));
+        }
+    }
+
+    let resolution = read_line(&mut reader)?;
+    let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", h, "+X", w] => (h.parse(), w.parse()),
+        _ => return Err(invalid("only -Y +X orientation is supported")),
+    };
+    let (height, width): (usize, usize) = (
+        height.map_err(|_| invalid("bad height"))?,
+        width.map_err(|_| invalid("bad width"))?,
+    );
+
+    pixel_count(width, height)?;
+    // read what is there and check it could hold the pixels, headers can claim any size
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let scanline_size = if (8..0x8000).contains(&width) {
+        // the scanline header, and runs of up to 127 in each component
+        4 + 4 * 2 * width.div_ceil(127)
+    } else {
+        4 * width
+    };
+    match scanline_size.checked_mul(height) {
+        Some(size) if size <= data.len() => {}
+        _ => return Err(invalid("missing pixels")),
+    }
+    let mut data = &data[..];
+    let mut pixels = Vec::new();
+    let mut scanline = vec![[0u8; 4]; width];
+    for _ in 0..height {
+        read_hdr_scanline(&mut data, &mut scanline)?;
+        pixels.extend(scanline.iter().map(|rgbe| {
+            if rgbe[3] == 0 {
+                Color::new(0.0, 0.0, 0.0)
+            } else {
+                let scale = 2f64.powi(rgbe[3] as i32 - 128 - 8);
+                Color::new(
+                    rgbe[0] as f64 * scale,
+                    rgbe[1] as f64 * scale,
+                    rgbe[2] as f64 * scale,
+                )
+            }
+        }));
+    }
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn read_hdr_scanline(reader: &mut impl Read, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let mut first = [0u8; 4];
+    reader.read_exact(&mut first)?;
+    let width = scanline.len();
+    let encoded =
+        first[0] == 2 && first[1] == 2 && first[2] & 0x80 == 0 && (8..0x8000).contains(&width);
+    if !encoded {
+        scanline[0] = first;
+        for pixel in &mut scanline[1..] {
+            reader.read_exact(pixel)?;
+        }
+        return Ok(());
+    }
+    if ((first[2] as usize) << 8 | first[3] as usize) != width {
+        return Err(invalid("scanline width mismatch"));
+    }
+
+    // each component is run length encoded separately
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0u8; 1];
+            reader.read_exact(&mut count)?;
+            let (run, count) = if count[0] > 128 {
+                (true, count[0] as usize - 128)
+            } else {
+                (false, count[0] as usize)
+            };
+            if count == 0 || x + count > width {
+                return Err(invalid("bad scanline run"));
+            }
+            if run {
+                let mut value = [0u8; 1];
+                reader.read_exact(&mut value)?;
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[channel] = value[0];
+                }
+            } else {
+                for pixel in &mut scanline[x..x + count] {
+                    let mut value = [0u8; 1];
+                    reader.read_exact(&mut value)?;
+                    pixel[channel] = value[0];
+                }
+            }
+            x += count;
+        }
+    }
+    Ok(())
+}
+
+/// Color PFM, rows are stored bottom to top.
+pub fn read_pfm(mut reader: impl BufRead) -> io::Result<Image> {
+    if read_line(&mut reader)? != "PF" {
+        return Err(invalid("only color PFM is supported"));
+    }
+    let size = read_line(&mut reader)?;
+    let mut size = size.split_whitespace().map(|s| s.parse::<usize>());
+    let (width, height) = match (size.next(), size.next()) {
+        (Some(Ok(w)), Some(Ok(h))) => (w, h),
+        _ => return Err(invalid("bad size")),
+    };
+    let scale: f64 = read_line(&mut reader)?
+        .parse()
+        .map_err(|_| invalid("bad scale"))?;
+
+    let size = pixel_count(width, height)?
+        .checked_mul(12)
+        .ok_or_else(|| invalid("image too large"))?;
+    // read what is there rather than allocating whatever size the header claims
+    let mut data = Vec::new();
+    reader.take(size as u64).read_to_end(&mut data)?;
+    if data.len() < size {
+        return Err(invalid("missing pixels"));
+    }
+    let value = |bytes: &[u8]| {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if scale < 0.0 {
+            f32::from_le_bytes(bytes) as f64
+        } else {
+            f32::from_be_bytes(bytes) as f64
+        }
+    };
+
+    let pixels = data
+        .chunks(width * 12)
+        .rev()
+        .flat_map(|row| row.chunks(12))
+        .map(|p| Color::new(value(&p[0..4]), value(&p[4..8]), value(&p[8..12])))
+        .collect();
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// 8 or 16-bit PNG in any color type, alpha is ignored.
+pub fn read_png(reader: impl Read, gamma_encoded: bool) -> io::Result<Image> {
+    let mut decoder = png::Decoder::new(reader);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(io::Error::other)?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).map_err(io::Error::other)?;
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    let channels = info.color_type.samples();
+    let sample = |bytes: &[u8], i: usize| match info.bit_depth {
+        png::BitDepth::Sixteen => {
+            u16::from_be_bytes([bytes[2 * i], bytes[2 * i + 1]]) as f64 / 65535.0
+        }
+        _ => bytes[i] as f64 / 255.0,
+    };
+    let bytes_per_pixel = channels
+        * if info.bit_depth == png::BitDepth::Sixteen {
+            2
+        } else {
+            1
+        };
+
+    let pixels = data[..info.buffer_size()]
+        .chunks(info.line_size)
+        .flat_map(|row| row[..width * bytes_per_pixel].chunks(bytes_per_pixel))
+        .map(|p| {
+            let c = if channels < 3 {
+                let v = sample(p, 0);
+                Color::new(v, v, v)
+            } else {
+                Color::new(sample(p, 0), sample(p, 1), sample(p, 2))
+            };
+            if gamma_encoded {
+                c.gamma_to_linear()
+            } else {
+                c
+            }
+        })
+        .collect();
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// First layer of an OpenEXR file with RGB channels.
+pub fn read_exr(path: &Path) -> io::Result<Image> {
+    let image = exr::prelude::read_first_rgba_layer_from_file(
+        path,
+        |resolution, _| Image {
+            width: resolution.width(),
+            height: resolution.height(),
+            pixels: vec![Color::new(0.0, 0.0, 0.0); resolution.width() * resolution.height()],
+        },
+        |image: &mut Image, position, (r, g, b, _): (f32, f32, f32, f32)| {
+            let width = image.width;
+            image.pixels[position.y() * width + position.x()] =
+                Color::new(r as f64, g as f64, b as f64);
+        },
+    )
+    .map_err(io::Error::other)?;
+    Ok(image.layer_data.channel_data.pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pfm(header: &str, values: &[f32]) -> Vec<u8> {
+        let mut data = header.as_bytes().to_vec();
+        data.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+        data
+    }
+
+    fn hdr(resolution: &str, pixels: &[[u8; 4]]) -> Vec<u8> {
+        let mut data =
+            format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{}\n", resolution).into_bytes();
+        data.extend(pixels.iter().flatten());
+        data
+    }
+
+    #[test]
+    fn reads_pfm_bottom_up() {
+        let data = pfm("PF\n1 2\n-1.0\n", &[0.0, 0.5, 1.0, 2.0, 4.0, 8.0]);
+        let image = read_pfm(&data[..]).unwrap();
+        assert_eq!((image.width, image.height), (1, 2));
+        let (top, bottom) = (image.pixel(0, 0), image.pixel(0, 1));
+        assert_eq!((top.r, top.g, top.b), (2.0, 4.0, 8.0));
+        assert_eq!((bottom.r, bottom.g, bottom.b), (0.0, 0.5, 1.0));
+    }
+
+    #[test]
+    fn rejects_degenerate_pfm() {
+        for header in ["PF\n0 4\n-1.0\n", "PF\n4 0\n-1.0\n"] {
+            assert!(read_pfm(&pfm(header, &[])[..]).is_err());
+        }
+        // too large to address, or to allocate before finding the pixels missing
+        let huge = pfm("PF\n4294967296 4294967296\n-1.0\n", &[]);
+        assert!(read_pfm(&huge[..]).is_err());
+        let truncated = pfm("PF\n1000000 1000000\n-1.0\n", &[1.0, 2.0, 3.0]);
+        assert!(read_pfm(&truncated[..]).is_err());
+    }
+
+    #[test]
+    fn reads_flat_hdr() {
+        let data = hdr("-Y 1 +X 2", &[[128, 64, 0, 129], [0, 0, 0, 0]]);
+        let image = read_hdr(&data[..]).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        let (lit, black) = (image.pixel(0, 0), image.pixel(1, 0));
+        assert_eq!((lit.r, lit.g, lit.b), (1.0, 0.5, 0.0));
+        assert_eq!((black.r, black.g, black.b), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn rejects_degenerate_hdr() {
+        for resolution in ["-Y 0 +X 4", "-Y 4 +X 0"] {
+            assert!(read_hdr(&hdr(resolution, &[])[..]).is_err());
+        }
+        let huge = hdr("-Y 4294967296 +X 4294967296", &[]);
+        assert!(read_hdr(&huge[..]).is_err());
+        let truncated = hdr("-Y 1000000 +X 1000", &[[1, 1, 1, 128]]);
+        assert!(read_hdr(&truncated[..]).is_err());
+        // a scanline too wide to allocate before finding its pixels missing
+        let wide = hdr("-Y 1 +X 1000000000000", &[[1, 1, 1, 128]]);
+        assert!(read_hdr(&wide[..]).is_err());
+    }
+}
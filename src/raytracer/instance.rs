@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use rand::rngs::SmallRng;
+
+use super::aabb::{self, Aabb};
+use super::interval::Interval;
+use super::materials::Material;
+use super::motion::Motion;
+use super::ray::*;
+use super::transform::Transform;
+use super::vec3::*;
+
+/// Shared object placed in the world by a transform, so the same geometry can appear many times.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    transform: Transform,
+    /// Translation applied after `transform`, for instances that move while the shutter is open
+    motion: Option<Motion>,
+    /// Replaces the object's own material
+    material: Option<Arc<Material>>,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Self {
+        Self {
+            object,
+            transform,
+            motion: None,
+            material: None,
+        }
+    }
+
+    pub fn with_motion(mut self, motion: Motion) -> Self {
+        self.motion = Some(motion);
+        self
+    }
+
+    /// Replaces the material of every part of the object, making it a light as a whole
+    /// if the material emits and none of it one otherwise.
+    pub fn with_material(mut self, material: Arc<Material>) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    fn offset(&self, time: f64) -> Vec3 {
+        match &self.motion {
+            Some(motion) => motion.at(time),
+            None => vec3!(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// A world space point in object space at `time`.
+    fn to_object(&self, point: &Vec3, time: f64) -> Vec3 {
+        self.transform.inverse().point(&(point - self.offset(time)))
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        // the direction is not normalized, so t is the same in both spaces
+        let object_ray = Ray::new(
+            self.to_object(&ray.origin, ray.time),
+            self.transform.inverse().vector(&ray.direction),
+            ray.time,
+        );
+        let mut hit = self.object.hit(&object_ray, ray_t)?;
+
+        hit.point = ray.at(hit.t);
+        // the inverse transpose keeps which side of the surface the ray is on
+        hit.normal = self.transform.normal(&hit.normal).unit_vec();
+        hit.geometric_normal = self.transform.normal(&hit.geometric_normal).unit_vec();
+        hit.dpdu = self.transform.matrix().vector(&hit.dpdu);
+        hit.dpdv = self.transform.matrix().vector(&hit.dpdv);
+        hit.dndu = self.transform.normal(&hit.dndu);
+        hit.dndv = self.transform.normal(&hit.dndv);
+        if let Some(material) = &self.material {
+            hit.material = material;
+        }
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let bbox = self.object.bounding_box();
+        if bbox.is_empty() {
+            return aabb::EMPTY;
+        }
+        let matrix = self.transform.matrix();
+        let mut transformed = aabb::EMPTY;
+        for corner in 0..8 {
+            let p = vec3!(
+                if corner & 1 == 0 {
+                    bbox.x.min
+                } else {
+                    bbox.x.max
+                },
+                if corner & 2 == 0 {
+                    bbox.y.min
+                } else {
+                    bbox.y.max
+                },
+                if corner & 4 == 0 {
+                    bbox.z.min
+                } else {
+                    bbox.z.max
+                }
+            );
+            let p = matrix.point(&p);
+            transformed = Aabb::enclosing(&transformed, &Aabb::from_points(p, p));
+        }
+        match &self.motion {
+            // every translation along the path added to every point of the box
+            Some(motion) => {
+                let path = motion.bounds();
+                let sweep =
+                    |a: &Interval, b: &Interval| Interval::new(a.min + b.min, a.max + b.max);
+                Aabb::new(
+                    sweep(&transformed.x, &path.x),
+                    sweep(&transformed.y, &path.y),
+                    sweep(&transformed.z, &path.z),
+                )
+            }
+            None => transformed,
+        }
+    }
+
+    fn is_light(&self) -> bool {
+        match &self.material {
+            Some(material) => material.is_emissive(),
+            None => self.object.is_light(),
+        }
+    }
+
+    /// The object's density, scaled by how much the transform stretches solid angles at `direction`.
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3, time: f64) -> f64 {
+        let inverse = self.transform.inverse();
+        let object_direction = inverse.vector(&direction.unit_vec());
+        let length = object_direction.length();
+        self.object
+            .pdf_value(&self.to_object(origin, time), &object_direction, time)
+            * inverse.determinant3().abs()
+            / (length * length * length)
+    }
+
+    fn random(&self, origin: &Vec3, time: f64, rng: &mut SmallRng) -> Vec3 {
+        let direction = self.object.random(&self.to_object(origin, time), time, rng);
+        self.transform.matrix().vector(&direction)
+    }
+
+    /// The object's lights placed the same way, none if the material replaces theirs.
+    fn lights(&self) -> Vec<Arc<dyn Hittable>> {
+        if self.material.is_some() {
+            return Vec::new();
+        }
+        self.object
+            .lights()
+            .into_iter()
+            .map(|light| {
+                let instance: Arc<dyn Hittable> = Arc::new(Self {
+                    object: light,
+                    transform: self.transform,
+                    motion: self.motion.clone(),
+                    material: None,
+                });
+                instance
+            })
+            .collect()
+    }
+}
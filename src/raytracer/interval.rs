@@ -1,3 +1,4 @@
+#[derive(Clone, Copy)]
 pub struct Interval {
     pub min: f64,
     pub max: f64,
@@ -8,8 +9,18 @@ impl Interval {
         Self { min, max }
     }
 
-    pub fn contains(&self, x: f64) -> bool {
-        self.min <= x && x <= self.max
+    /// Smallest interval containing both `a` and `b`.
+    pub fn enclosing(a: &Interval, b: &Interval) -> Self {
+        Self::new(a.min.min(b.min), a.max.max(b.max))
+    }
+
+    pub fn size(&self) -> f64 {
+        self.max - self.min
+    }
+
+    /// Widens the interval by `delta` in total.
+    pub fn expand(&self, delta: f64) -> Self {
+        Self::new(self.min - delta / 2.0, self.max + delta / 2.0)
     }
 
     pub fn surrounds(&self, x: f64) -> bool {
@@ -17,12 +28,7 @@ impl Interval {
     }
 }
 
-const EMPTY: Interval = Interval {
+pub const EMPTY: Interval = Interval {
     min: f64::INFINITY,
     max: f64::NEG_INFINITY,
 };
-
-const UNIVERSE: Interval = Interval {
-    min: f64::NEG_INFINITY,
-    max: f64::INFINITY,
-};
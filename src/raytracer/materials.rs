@@ -1,8 +1,14 @@
-use rand::rngs::ThreadRng;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::rngs::SmallRng;
 use rand::Rng;
 
+use super::bump::BumpMap;
 use super::color::Color;
+use super::microfacet::TrowbridgeReitz;
 use super::ray::*;
+use super::texture::{SolidColor, Texture};
 use super::vec3::Vec3;
 
 pub trait Scatterable {
@@ -10,14 +16,74 @@ pub trait Scatterable {
         &self,
         ray: &Ray,
         hit_record: &HitRecord,
-        rng: &mut ThreadRng,
+        rng: &mut SmallRng,
     ) -> Option<(Ray, Color)>;
+
+    /// Light given off at the hit point, black for anything but lights.
+    fn emitted(&self, _hit_record: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// Fraction of the light arriving from `direction` that leaves along the reverse of `ray`,
+    /// including the cosine term. `scatter` returns this divided by `pdf` as attenuation.
+    fn eval(&self, _ray: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// Density of `scatter` picking `direction`, in solid angle. Zero for mirror-like
+    /// materials, whose directions can not be hit by sampling lights.
+    fn pdf(&self, _ray: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> f64 {
+        0.0
+    }
 }
 
 pub enum Material {
     Lambertian(Lambertian),
     Metal(Metal),
     Glass(Glass),
+    DiffuseLight(DiffuseLight),
+    Principled(Principled),
+    Bumped(Bumped),
+}
+
+impl Material {
+    /// Objects made of emissive materials are sampled as lights.
+    pub fn is_emissive(&self) -> bool {
+        match self {
+            Material::DiffuseLight(_) => true,
+            Material::Bumped(m) => m.material.is_emissive(),
+            _ => false,
+        }
+    }
+
+    /// Whether light of different wavelengths scatters in different directions.
+    pub fn is_dispersive(&self) -> bool {
+        match self {
+            Material::Glass(m) => m.index_of_refraction.is_dispersive(),
+            Material::Bumped(m) => m.material.is_dispersive(),
+            _ => false,
+        }
+    }
+
+    /// Whether the material only scatters in exact directions, like a mirror, or not at all.
+    /// Directions towards lights never are one of them, so lights are not sampled for it.
+    pub fn is_specular(&self) -> bool {
+        match self {
+            Material::Lambertian(_) | Material::Principled(_) => false,
+            Material::Metal(m) => m.distribution.is_smooth(),
+            Material::Glass(m) => m.distribution.is_smooth(),
+            Material::DiffuseLight(_) => true,
+            Material::Bumped(m) => m.material.is_specular(),
+        }
+    }
+
+    /// Normal to shade the hit with instead of the surface's own, on its outside.
+    pub fn shading_normal(&self, hit_record: &HitRecord) -> Option<Vec3> {
+        match self {
+            Material::Bumped(m) => Some(m.bump_map.shading_normal(hit_record)),
+            _ => None,
+        }
+    }
 }
 
 impl Scatterable for Material {
@@ -25,22 +91,66 @@ impl Scatterable for Material {
         &self,
         ray: &Ray,
         hit_record: &HitRecord,
-        rng: &mut ThreadRng,
+        rng: &mut SmallRng,
     ) -> Option<(Ray, Color)> {
         match self {
             Material::Lambertian(m) => m.scatter(ray, hit_record, rng),
             Material::Metal(m) => m.scatter(ray, hit_record, rng),
             Material::Glass(m) => m.scatter(ray, hit_record, rng),
+            Material::DiffuseLight(m) => m.scatter(ray, hit_record, rng),
+            Material::Principled(m) => m.scatter(ray, hit_record, rng),
+            Material::Bumped(m) => m.material.scatter(ray, hit_record, rng),
         }
     }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Color {
+        match self {
+            Material::Lambertian(m) => m.emitted(hit_record),
+            Material::Metal(m) => m.emitted(hit_record),
+            Material::Glass(m) => m.emitted(hit_record),
+            Material::DiffuseLight(m) => m.emitted(hit_record),
+            Material::Principled(m) => m.emitted(hit_record),
+            Material::Bumped(m) => m.material.emitted(hit_record),
+        }
+    }
+
+    fn eval(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        match self {
+            Material::Lambertian(m) => m.eval(ray, hit_record, direction),
+            Material::Metal(m) => m.eval(ray, hit_record, direction),
+            Material::Glass(m) => m.eval(ray, hit_record, direction),
+            Material::DiffuseLight(m) => m.eval(ray, hit_record, direction),
+            Material::Principled(m) => m.eval(ray, hit_record, direction),
+            Material::Bumped(m) => m.material.eval(ray, hit_record, direction),
+        }
+    }
+
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        match self {
+            Material::Lambertian(m) => m.pdf(ray, hit_record, direction),
+            Material::Metal(m) => m.pdf(ray, hit_record, direction),
+            Material::Glass(m) => m.pdf(ray, hit_record, direction),
+            Material::DiffuseLight(m) => m.pdf(ray, hit_record, direction),
+            Material::Principled(m) => m.pdf(ray, hit_record, direction),
+            Material::Bumped(m) => m.material.pdf(ray, hit_record, direction),
+        }
+    }
+}
+
+fn albedo(texture: &Arc<dyn Texture>, hit_record: &HitRecord) -> Color {
+    texture.value(hit_record)
 }
 
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
+        Self::textured(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Self {
         Self { albedo }
     }
 }
@@ -48,32 +158,151 @@ impl Lambertian {
 impl Scatterable for Lambertian {
     fn scatter(
         &self,
-        _ray: &Ray,
+        ray: &Ray,
         hit_record: &HitRecord,
-        rng: &mut ThreadRng,
+        rng: &mut SmallRng,
     ) -> Option<(Ray, Color)> {
         let mut scatter_direction = hit_record.normal + Vec3::random_unit_vec(rng);
         if scatter_direction.near_zero() {
             scatter_direction = hit_record.normal;
         }
         let target = hit_record.point + scatter_direction;
-        let scattered = Ray::new(hit_record.point, target - hit_record.point);
-        Some((scattered, self.albedo))
+        let scattered = Ray::new(hit_record.point, target - hit_record.point, ray.time);
+        Some((scattered, albedo(&self.albedo, hit_record)))
+    }
+
+    fn eval(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        self.pdf(ray, hit_record, direction) * albedo(&self.albedo, hit_record)
+    }
+
+    /// Scattering around the normal through a point on the unit sphere is cosine weighted.
+    fn pdf(&self, _ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        hit_record.normal.dot(&direction.unit_vec()).max(0.0) / PI
     }
 }
 
+/// Conductor with a rough surface of GGX microfacets, a mirror when perfectly smooth.
 pub struct Metal {
-    albedo: Color,
-    fuzz: f64,
+    reflectance: Reflectance,
+    distribution: TrowbridgeReitz,
+}
+
+/// How much light a metal reflects depending on the angle it is seen at.
+enum Reflectance {
+    /// Schlick's approximation from the color at normal incidence
+    Albedo(Arc<dyn Texture>),
+    /// Exact for a complex index of refraction `eta + i k` per color channel
+    Conductor { eta: Color, k: Color },
+}
+
+impl Reflectance {
+    fn fresnel(&self, hit_record: &HitRecord, cosine: f64) -> Color {
+        match self {
+            Reflectance::Albedo(texture) => {
+                let f0 = albedo(texture, hit_record);
+                let weight = (1.0 - cosine).powi(5);
+                (1.0 - weight) * f0 + weight * Color::new(1.0, 1.0, 1.0)
+            }
+            Reflectance::Conductor { eta, k } => Color::new(
+                fresnel_conductor(cosine, eta.r, k.r),
+                fresnel_conductor(cosine, eta.g, k.g),
+                fresnel_conductor(cosine, eta.b, k.b),
+            ),
+        }
+    }
+}
+
+/// Fraction of unpolarized light reflected by a conductor, `cosine` away from its normal.
+fn fresnel_conductor(cosine: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cosine * cosine;
+    let sin2 = 1.0 - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cosine * a;
+    let rs = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+    0.5 * (rp + rs)
+}
+
+/// Measured metals, by their complex index of refraction for red, green and blue light.
+#[derive(Clone, Copy)]
+pub enum Conductor {
+    Gold,
+    Silver,
+    Copper,
+    Aluminium,
+    Chromium,
+    Iron,
+}
+
+impl Conductor {
+    /// Real part `eta` and imaginary part `k` of the index of refraction.
+    pub fn ior(&self) -> (Color, Color) {
+        let (eta, k) = match self {
+            Conductor::Gold => ([0.143, 0.375, 1.442], [3.983, 2.386, 1.603]),
+            Conductor::Silver => ([0.155, 0.117, 0.138], [4.828, 3.122, 2.147]),
+            Conductor::Copper => ([0.200, 0.924, 1.102], [3.913, 2.453, 2.142]),
+            Conductor::Aluminium => ([1.657, 0.880, 0.521], [9.224, 6.270, 4.837]),
+            Conductor::Chromium => ([4.370, 2.917, 1.655], [5.206, 4.231, 3.755]),
+            Conductor::Iron => ([2.870, 2.950, 2.650], [3.120, 2.930, 2.810]),
+        };
+        (
+            Color::new(eta[0], eta[1], eta[2]),
+            Color::new(k[0], k[1], k[2]),
+        )
+    }
 }
 
 impl Metal {
-    pub fn new(albedo: Color, fuzz: f64) -> Self {
+    /// `roughness` in [0, 1], from a mirror to a dull surface.
+    pub fn new(albedo: Color, roughness: f64) -> Self {
+        Self::textured(Arc::new(SolidColor::new(albedo)), roughness)
+    }
+
+    /// Tinted by the texture's color, which is the reflectance seen head on.
+    pub fn textured(albedo: Arc<dyn Texture>, roughness: f64) -> Self {
+        Self::with_reflectance(Reflectance::Albedo(albedo), roughness)
+    }
+
+    pub fn conductor(eta: Color, k: Color, roughness: f64) -> Self {
+        Self::with_reflectance(Reflectance::Conductor { eta, k }, roughness)
+    }
+
+    fn with_reflectance(reflectance: Reflectance, roughness: f64) -> Self {
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness);
         Self {
-            albedo,
-            fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
+            reflectance,
+            distribution: TrowbridgeReitz::new(alpha, alpha),
         }
     }
+
+    /// Different roughness along the surface's `u` and `v` directions, like brushed metal.
+    pub fn with_anisotropic_roughness(mut self, roughness_u: f64, roughness_v: f64) -> Self {
+        self.distribution = TrowbridgeReitz::new(
+            TrowbridgeReitz::roughness_to_alpha(roughness_u),
+            TrowbridgeReitz::roughness_to_alpha(roughness_v),
+        );
+        self
+    }
+}
+
+/// Tangent, bitangent and normal, the axes of the local frame microfacets are described in.
+fn frame(hit_record: &HitRecord) -> (Vec3, Vec3, Vec3) {
+    let (tangent, bitangent) = hit_record.tangents(&hit_record.normal);
+    (tangent, bitangent, hit_record.normal)
+}
+
+/// Coordinates of `v` in the frame of `axes`.
+fn to_local(axes: &(Vec3, Vec3, Vec3), v: &Vec3) -> Vec3 {
+    Vec3::new(v.dot(&axes.0), v.dot(&axes.1), v.dot(&axes.2))
+}
+
+fn to_world(axes: &(Vec3, Vec3, Vec3), v: &Vec3) -> Vec3 {
+    v.x * axes.0 + v.y * axes.1 + v.z * axes.2
 }
 
 impl Scatterable for Metal {
@@ -81,65 +310,832 @@ impl Scatterable for Metal {
         &self,
         ray: &Ray,
         hit_record: &HitRecord,
-        rng: &mut ThreadRng,
+        rng: &mut SmallRng,
     ) -> Option<(Ray, Color)> {
-        let reflected = ray.direction.unit_vec().reflect(&hit_record.normal);
-        let scattered = Ray::new(
-            hit_record.point,
-            reflected + self.fuzz * Vec3::random_unit_vec(rng),
-        );
-        if scattered.direction.dot(&hit_record.normal) > 0.0 {
-            Some((scattered, self.albedo))
-        } else {
-            None
+        let unit_direction = ray.direction.unit_vec();
+        let cos_theta = (-unit_direction).dot(&hit_record.normal);
+        if cos_theta <= 0.0 {
+            return None;
+        }
+        if self.distribution.is_smooth() {
+            let reflected = unit_direction.reflect(&hit_record.normal);
+            let scattered = Ray::new(hit_record.point, reflected, ray.time)
+                .with_differential(hit_record.reflected_differential(ray));
+            return Some((scattered, self.reflectance.fresnel(hit_record, cos_theta)));
+        }
+
+        let axes = frame(hit_record);
+        let wo = to_local(&axes, &-unit_direction);
+        let wm = self
+            .distribution
+            .sample_visible(&wo, (rng.gen(), rng.gen()));
+        let wi = (-wo).reflect(&wm);
+        if wi.z <= 0.0 {
+            return None;
+        }
+        // the distribution and most of the masking cancel against the density
+        let weight = self.distribution.g(&wo, &wi) / self.distribution.g1(&wo);
+        let scattered = Ray::new(hit_record.point, to_world(&axes, &wi), ray.time);
+        Some((
+            scattered,
+            weight * self.reflectance.fresnel(hit_record, wo.dot(&wm)),
+        ))
+    }
+
+    fn eval(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        if self.distribution.is_smooth() {
+            return black;
+        }
+        let axes = frame(hit_record);
+        let wo = to_local(&axes, &-ray.direction.unit_vec());
+        let wi = to_local(&axes, &direction.unit_vec());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return black;
+        }
+        let wm = wo + wi;
+        if wm.near_zero() {
+            return black;
         }
+        let wm = wm.unit_vec();
+        let d = self.distribution.d(&wm);
+        let g = self.distribution.g(&wo, &wi);
+        (d * g / (4.0 * wo.z)) * self.reflectance.fresnel(hit_record, wo.dot(&wm))
+    }
+
+    /// Visible normals reflected about, converted to the density of the reflected direction.
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        if self.distribution.is_smooth() {
+            return 0.0;
+        }
+        let axes = frame(hit_record);
+        let wo = to_local(&axes, &-ray.direction.unit_vec());
+        let wi = to_local(&axes, &direction.unit_vec());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let wm = wo + wi;
+        if wm.near_zero() {
+            return 0.0;
+        }
+        let wm = wm.unit_vec();
+        self.distribution.pdf(&wo, &wm) / (4.0 * wo.dot(&wm))
+    }
+}
+
+/// Wavelength in nanometers indices of refraction are given at when they vary with it,
+/// for paths without a wavelength. The helium d line, which glasses are commonly specified at.
+const D_LINE: f64 = 587.6;
+
+/// Index of refraction by wavelength.
+#[derive(Clone, Copy)]
+pub enum RefractiveIndex {
+    Constant(f64),
+    /// Cauchy's equation `a + b / λ²`, with λ in micrometers
+    Cauchy {
+        a: f64,
+        b: f64,
+    },
+    /// Sellmeier equation `√(1 + Σ bᵢ λ² / (λ² - cᵢ))`, with λ in micrometers
+    Sellmeier {
+        b: [f64; 3],
+        c: [f64; 3],
+    },
+}
+
+impl RefractiveIndex {
+    /// Index at `wavelength` in nanometers.
+    pub fn at(&self, wavelength: f64) -> f64 {
+        let lambda2 = (wavelength / 1000.0).powi(2);
+        match self {
+            RefractiveIndex::Constant(index) => *index,
+            RefractiveIndex::Cauchy { a, b } => a + b / lambda2,
+            RefractiveIndex::Sellmeier { b, c } => (1.0
+                + (0..3)
+                    .map(|i| b[i] * lambda2 / (lambda2 - c[i]))
+                    .sum::<f64>())
+            .sqrt(),
+        }
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, RefractiveIndex::Constant(_))
+    }
+}
+
+/// Measured glasses and gems, by their Sellmeier coefficients.
+#[derive(Clone, Copy)]
+pub enum Dielectric {
+    /// Borosilicate crown glass, common optical glass
+    Bk7,
+    FusedSilica,
+    /// Dense flint glass, which disperses strongly
+    Sf11,
+    Diamond,
+}
+
+impl Dielectric {
+    pub fn index(&self) -> RefractiveIndex {
+        let (b, c) = match self {
+            Dielectric::Bk7 => (
+                [1.039_612_12, 0.231_792_344, 1.010_469_45],
+                [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+            ),
+            Dielectric::FusedSilica => (
+                [0.696_166_3, 0.407_942_6, 0.897_479_4],
+                [0.004_679_148_26, 0.013_512_063_1, 97.934_002_5],
+            ),
+            Dielectric::Sf11 => (
+                [1.737_596_95, 0.313_747_346, 1.898_781_01],
+                [0.013_188_707, 0.062_306_814_2, 155.236_29],
+            ),
+            Dielectric::Diamond => ([4.3356, 0.3306, 0.0], [0.011_236, 0.030_625, 0.0]),
+        };
+        RefractiveIndex::Sellmeier { b, c }
     }
 }
 
+/// Dielectric interface, smooth or with a rough surface of GGX microfacets like frosted glass.
+/// Refracts each wavelength differently if its index of refraction varies with them.
 pub struct Glass {
-    index_of_refraction: f64,
+    index_of_refraction: RefractiveIndex,
+    distribution: TrowbridgeReitz,
+    /// Fraction of each color absorbed per unit of distance travelled inside
+    absorption: Color,
 }
 
 impl Glass {
     pub fn new(index_of_refraction: f64) -> Self {
+        Self::dispersive(RefractiveIndex::Constant(index_of_refraction))
+    }
+
+    pub fn dispersive(index_of_refraction: RefractiveIndex) -> Self {
         Self {
             index_of_refraction,
+            distribution: TrowbridgeReitz::new(0.0, 0.0),
+            absorption: Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Colors light travelling inside, leaving `tint` of it after a distance of 1 / `density`.
+    pub fn with_absorption(mut self, tint: Color, density: f64) -> Self {
+        let coefficient = |c: f64| -c.clamp(1.0e-6, 1.0).ln() * density.max(0.0);
+        self.absorption = Color::new(
+            coefficient(tint.r),
+            coefficient(tint.g),
+            coefficient(tint.b),
+        );
+        self
+    }
+
+    /// Fraction of the light left after travelling from the ray's origin to the hit inside,
+    /// all of it for rays arriving from outside.
+    fn transmittance(&self, ray: &Ray, hit_record: &HitRecord) -> Color {
+        if hit_record.front_face {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+        let distance = hit_record.t * ray.direction.length();
+        Color::new(
+            (-self.absorption.r * distance).exp(),
+            (-self.absorption.g * distance).exp(),
+            (-self.absorption.b * distance).exp(),
+        )
+    }
+
+    /// `roughness` in [0, 1], from clear to heavily frosted.
+    pub fn with_roughness(mut self, roughness: f64) -> Self {
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness);
+        self.distribution = TrowbridgeReitz::new(alpha, alpha);
+        self
+    }
+
+    /// Index of refraction of the side the ray goes into over the side it comes from,
+    /// at the ray's wavelength.
+    fn eta(&self, ray: &Ray, hit_record: &HitRecord) -> f64 {
+        let index = self
+            .index_of_refraction
+            .at(ray.wavelength.unwrap_or(D_LINE));
+        if hit_record.front_face {
+            index
+        } else {
+            1.0 / index
         }
     }
 
-    fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
-        // Schlick's approximation for reflectance
-        let r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
-        r0 * r0 + (1.0 - r0 * r0) * (1.0 - cosine).powi(5)
+    fn scatter_smooth(&self, ray: &Ray, hit_record: &HitRecord, rng: &mut SmallRng) -> Ray {
+        let refraction_ratio = 1.0 / self.eta(ray, hit_record);
+        let cos_theta = (-ray.direction.unit_vec()).dot(&hit_record.normal).min(1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let cannot_refract = refraction_ratio * sin_theta > 1.0;
+        if cannot_refract
+            || fresnel_dielectric(cos_theta, self.eta(ray, hit_record)) > rng.gen::<f64>()
+        {
+            let direction = ray.direction.unit_vec().reflect(&hit_record.normal);
+            Ray::new(hit_record.point, direction, ray.time)
+                .with_differential(hit_record.reflected_differential(ray))
+        } else {
+            let direction = ray
+                .direction
+                .unit_vec()
+                .refract(&hit_record.normal, refraction_ratio);
+            Ray::new(hit_record.point, direction, ray.time)
+                .with_differential(hit_record.refracted_differential(ray, refraction_ratio))
+        }
+    }
+}
+
+/// Fraction of unpolarized light reflected by a dielectric interface, `cosine` away from its
+/// normal, with `eta` the ratio of the indices of refraction across it. One past the critical angle.
+fn fresnel_dielectric(cosine: f64, eta: f64) -> f64 {
+    let sin2_t = (1.0 - cosine * cosine) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
     }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let parallel = (eta * cosine - cos_t) / (eta * cosine + cos_t);
+    let perpendicular = (cosine - eta * cos_t) / (cosine + eta * cos_t);
+    0.5 * (parallel * parallel + perpendicular * perpendicular)
 }
 
+/// Microfacet normal that scatters `wo` into `wi` across a dielectric interface in the local
+/// frame, facing up, and whether it reflects. None for directions no microfacet produces.
+fn dielectric_half_vector(wo: &Vec3, wi: &Vec3, eta: f64) -> Option<(Vec3, bool)> {
+    if wo.z <= 0.0 || wi.z == 0.0 {
+        return None;
+    }
+    let reflect = wi.z > 0.0;
+    let wm = if reflect { wi + wo } else { eta * wi + wo };
+    if wm.near_zero() {
+        return None;
+    }
+    let wm = wm.unit_vec();
+    let wm = if wm.z < 0.0 { -wm } else { wm };
+    // both directions have to be on the side of the microfacet their hemisphere is on
+    if wm.dot(wi) * wi.z < 0.0 || wm.dot(wo) * wo.z < 0.0 {
+        return None;
+    }
+    Some((wm, reflect))
+}
+
+/// Direction light arriving along `wo` leaves a rough dielectric interface in, reflected or
+/// refracted about a visible microfacet by the chance of each. None if it ends up on the wrong side.
+fn sample_rough_dielectric(
+    distribution: &TrowbridgeReitz,
+    wo: &Vec3,
+    eta: f64,
+    rng: &mut SmallRng,
+) -> Option<Vec3> {
+    let wm = distribution.sample_visible(wo, (rng.gen(), rng.gen()));
+    if fresnel_dielectric(wo.dot(&wm), eta) > rng.gen::<f64>() {
+        Some((-wo).reflect(&wm)).filter(|wi| wi.z > 0.0)
+    } else {
+        Some((-wo).refract(&wm, 1.0 / eta)).filter(|wi| wi.z < 0.0)
+    }
+}
+
+/// BSDF of a rough dielectric interface times the cosine, for light arriving from `wi` that
+/// leaves along `wo` in the local frame, and the density of `sample_rough_dielectric` picking `wi`.
+fn rough_dielectric(distribution: &TrowbridgeReitz, wo: &Vec3, wi: &Vec3, eta: f64) -> (f64, f64) {
+    let Some((wm, reflect)) = dielectric_half_vector(wo, wi, eta) else {
+        return (0.0, 0.0);
+    };
+    let d = distribution.d(&wm);
+    let g = distribution.g(wo, wi);
+    let pdf = distribution.pdf(wo, &wm);
+    let reflectance = fresnel_dielectric(wo.dot(&wm), eta);
+    if reflect {
+        (
+            d * g * reflectance / (4.0 * wo.z),
+            reflectance * pdf / (4.0 * wo.dot(&wm)),
+        )
+    } else {
+        let denom = wi.dot(&wm) + wo.dot(&wm) / eta;
+        let jacobian = wi.dot(&wm).abs() / (denom * denom);
+        (
+            (1.0 - reflectance) * d * g * wo.dot(&wm).abs() * jacobian / wo.z,
+            (1.0 - reflectance) * pdf * jacobian,
+        )
+    }
+}
+
+// Radiance is not scaled by the squared ratio of the indices of refraction when it is
+// transmitted, the same as for smooth glass. The factors cancel for closed objects.
 impl Scatterable for Glass {
     fn scatter(
         &self,
         ray: &Ray,
         hit_record: &HitRecord,
-        rng: &mut ThreadRng,
+        rng: &mut SmallRng,
     ) -> Option<(Ray, Color)> {
-        let refraction_ratio = if hit_record.front_face {
+        if self.distribution.is_smooth() {
+            let scattered = self.scatter_smooth(ray, hit_record, rng);
+            return Some((scattered, self.transmittance(ray, hit_record)));
+        }
+
+        let eta = self.eta(ray, hit_record);
+        let axes = frame(hit_record);
+        let wo = to_local(&axes, &-ray.direction.unit_vec());
+        if wo.z <= 0.0 {
+            return None;
+        }
+        // picking reflection or transmission by the Fresnel term cancels it from the weight
+        let wi = sample_rough_dielectric(&self.distribution, &wo, eta, rng)?;
+        let weight = self.distribution.g(&wo, &wi) / self.distribution.g1(&wo);
+        let scattered = Ray::new(hit_record.point, to_world(&axes, &wi), ray.time);
+        Some((scattered, weight * self.transmittance(ray, hit_record)))
+    }
+
+    fn eval(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        if self.distribution.is_smooth() {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let eta = self.eta(ray, hit_record);
+        let axes = frame(hit_record);
+        let wo = to_local(&axes, &-ray.direction.unit_vec());
+        let wi = to_local(&axes, &direction.unit_vec());
+        let (value, _) = rough_dielectric(&self.distribution, &wo, &wi, eta);
+        value * self.transmittance(ray, hit_record)
+    }
+
+    /// Visible normals reflected or refracted about, converted to the density of the
+    /// scattered direction, and weighted by the chance of each.
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        if self.distribution.is_smooth() {
+            return 0.0;
+        }
+        let eta = self.eta(ray, hit_record);
+        let axes = frame(hit_record);
+        let wo = to_local(&axes, &-ray.direction.unit_vec());
+        let wi = to_local(&axes, &direction.unit_vec());
+        rough_dielectric(&self.distribution, &wo, &wi, eta).1
+    }
+}
+
+/// Index of refraction of the principled material's clearcoat, a thin layer of varnish.
+const CLEARCOAT_IOR: f64 = 1.5;
+
+/// Smoothest GGX the principled material's lobes get, so they can always be sampled and mixed.
+/// Anything smoother looks the same as a mirror.
+const PRINCIPLED_MIN_ALPHA: f64 = 1.0e-3;
+
+/// Disney's principled BSDF, one material for plastics, metals, fabrics, varnished and
+/// translucent surfaces driven by parameters in [0, 1]. It blends a diffuse lobe with
+/// a white sheen, a GGX specular lobe, a rough glass lobe and a clearcoat on top.
+pub struct Principled {
+    base_color: Arc<dyn Texture>,
+    metallic: f64,
+    roughness: f64,
+    distribution: TrowbridgeReitz,
+    /// Reflectance of the dielectric base seen head on is 0.08 of this, 0.5 is that of ior 1.5
+    specular: f64,
+    sheen: f64,
+    clearcoat: f64,
+    clearcoat_distribution: TrowbridgeReitz,
+    transmission: f64,
+    index_of_refraction: f64,
+    /// Flattens the diffuse lobe, approximating light scattered just under the surface
+    subsurface: f64,
+}
+
+impl Principled {
+    /// A plastic until other parameters are set: dielectric, specular 0.5 and roughness 0.5.
+    pub fn textured(base_color: Arc<dyn Texture>) -> Self {
+        Self {
+            base_color,
+            metallic: 0.0,
+            roughness: 0.0,
+            distribution: TrowbridgeReitz::new(0.0, 0.0),
+            specular: 0.5,
+            sheen: 0.0,
+            clearcoat: 0.0,
+            clearcoat_distribution: TrowbridgeReitz::new(0.0, 0.0),
+            transmission: 0.0,
+            index_of_refraction: 1.5,
+            subsurface: 0.0,
+        }
+        .with_roughness(0.5)
+        .with_clearcoat(0.0, 0.1)
+    }
+
+    /// From a dielectric to a metal tinted by the base color.
+    pub fn with_metallic(mut self, metallic: f64) -> Self {
+        self.metallic = metallic.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Roughness of the specular and glass lobes, which also adds retro-reflection to the diffuse one.
+    pub fn with_roughness(mut self, roughness: f64) -> Self {
+        self.roughness = roughness.clamp(0.0, 1.0);
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness).max(PRINCIPLED_MIN_ALPHA);
+        self.distribution = TrowbridgeReitz::new(alpha, alpha);
+        self
+    }
+
+    pub fn with_specular(mut self, specular: f64) -> Self {
+        self.specular = specular.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Soft white highlights at grazing angles, like on cloth.
+    pub fn with_sheen(mut self, sheen: f64) -> Self {
+        self.sheen = sheen.clamp(0.0, 1.0);
+        self
+    }
+
+    /// A clear varnish over the rest of the material, with a roughness of its own.
+    pub fn with_clearcoat(mut self, clearcoat: f64, roughness: f64) -> Self {
+        self.clearcoat = clearcoat.clamp(0.0, 1.0);
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness).max(PRINCIPLED_MIN_ALPHA);
+        self.clearcoat_distribution = TrowbridgeReitz::new(alpha, alpha);
+        self
+    }
+
+    /// From opaque to glass tinted by the base color, refracting with `index_of_refraction`.
+    pub fn with_transmission(mut self, transmission: f64, index_of_refraction: f64) -> Self {
+        self.transmission = transmission.clamp(0.0, 1.0);
+        self.index_of_refraction = index_of_refraction;
+        self
+    }
+
+    pub fn with_subsurface(mut self, subsurface: f64) -> Self {
+        self.subsurface = subsurface.clamp(0.0, 1.0);
+        self
+    }
+
+    /// How much of the diffuse, specular, glass and clearcoat lobes is in the material.
+    fn weights(&self) -> [f64; 4] {
+        let dielectric = 1.0 - self.metallic;
+        [
+            dielectric * (1.0 - self.transmission),
+            1.0 - dielectric * self.transmission,
+            dielectric * self.transmission,
+            self.clearcoat,
+        ]
+    }
+
+    /// Schlick's approximation, from the specular reflectance of the dielectric base blended
+    /// into the base color as the material turns metallic.
+    fn specular_fresnel(&self, base_color: &Color, cosine: f64) -> Color {
+        let dielectric = 0.08 * self.specular * Color::new(1.0, 1.0, 1.0);
+        let f0 = (1.0 - self.metallic) * dielectric + self.metallic * base_color;
+        let weight = schlick_weight(cosine);
+        (1.0 - weight) * f0 + weight * Color::new(1.0, 1.0, 1.0)
+    }
+
+    /// Chance of sampling each lobe, roughly in proportion to the light it scatters towards `wo`.
+    fn probabilities(&self, base_color: &Color, wo: &Vec3) -> [f64; 4] {
+        let weights = self.weights();
+        let probabilities = [
+            weights[0] * (base_color.luminance() + self.sheen),
+            weights[1] * self.specular_fresnel(base_color, wo.z).luminance(),
+            weights[2],
+            weights[3] * fresnel_dielectric(wo.z, CLEARCOAT_IOR),
+        ];
+        let total: f64 = probabilities.iter().sum();
+        if total <= 0.0 {
+            return [1.0, 0.0, 0.0, 0.0];
+        }
+        probabilities.map(|p| p / total)
+    }
+
+    /// Index of refraction of the side the ray goes into over the side it comes from.
+    fn eta(&self, hit_record: &HitRecord) -> f64 {
+        if hit_record.front_face {
+            self.index_of_refraction
+        } else {
             1.0 / self.index_of_refraction
+        }
+    }
+
+    /// Sum of the lobes, including the cosine term, in the local frame.
+    fn eval_local(&self, base_color: &Color, wo: &Vec3, wi: &Vec3, eta: f64) -> Color {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let [diffuse, specular, glass, clearcoat] = self.weights();
+        let mut value = Color::new(0.0, 0.0, 0.0);
+        let mut coat = Color::new(0.0, 0.0, 0.0);
+        if wo.z > 0.0 && wi.z > 0.0 {
+            let wm = (wo + wi).unit_vec();
+            let cos_d = wi.dot(&wm);
+            if diffuse > 0.0 {
+                let (fo, fi) = (schlick_weight(wo.z), schlick_weight(wi.z));
+                // rough surfaces reflect more back towards the light at grazing angles
+                let retro = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+                let lambert = (1.0 + (retro - 1.0) * fo) * (1.0 + (retro - 1.0) * fi);
+                // Hanrahan-Krueger, light scattered once in a thin layer under the surface
+                let flat = self.roughness * cos_d * cos_d;
+                let single = (1.0 + (flat - 1.0) * fo) * (1.0 + (flat - 1.0) * fi);
+                let subsurface = 1.25 * (single * (1.0 / (wo.z + wi.z) - 0.5) + 0.5);
+                let shape = (1.0 - self.subsurface) * lambert + self.subsurface * subsurface;
+                value += diffuse
+                    * wi.z
+                    * ((shape / PI) * base_color + self.sheen * schlick_weight(cos_d) * white);
+            }
+            let d = self.distribution.d(&wm);
+            let g = self.distribution.g(wo, wi);
+            value +=
+                (specular * d * g / (4.0 * wo.z)) * self.specular_fresnel(base_color, wo.dot(&wm));
+            let d = self.clearcoat_distribution.d(&wm);
+            let g = self.clearcoat_distribution.g(wo, wi);
+            let reflectance = fresnel_dielectric(wo.dot(&wm), CLEARCOAT_IOR);
+            coat = (clearcoat * d * g * reflectance / (4.0 * wo.z)) * white;
+        }
+        if glass > 0.0 {
+            let (bsdf, _) = rough_dielectric(&self.distribution, wo, wi, eta);
+            let tint = if wi.z < 0.0 { *base_color } else { white };
+            value += (glass * bsdf) * tint;
+        }
+        // the light the clearcoat reflects does not reach the rest
+        let through = 1.0 - clearcoat * fresnel_dielectric(wo.z, CLEARCOAT_IOR);
+        through * value + coat
+    }
+
+    /// Density of sampling `wi`, mixed from each lobe's by the chance of picking it.
+    fn pdf_local(&self, probabilities: &[f64; 4], wo: &Vec3, wi: &Vec3, eta: f64) -> f64 {
+        let mut pdf = 0.0;
+        if wo.z > 0.0 && wi.z > 0.0 {
+            let wm = (wo + wi).unit_vec();
+            let reflected =
+                |distribution: &TrowbridgeReitz| distribution.pdf(wo, &wm) / (4.0 * wo.dot(&wm));
+            pdf += probabilities[0] * wi.z / PI
+                + probabilities[1] * reflected(&self.distribution)
+                + probabilities[3] * reflected(&self.clearcoat_distribution);
+        }
+        if probabilities[2] > 0.0 {
+            pdf += probabilities[2] * rough_dielectric(&self.distribution, wo, wi, eta).1;
+        }
+        pdf
+    }
+}
+
+/// Weight of white in Schlick's approximation of Fresnel reflectance, `cosine` away from the normal.
+fn schlick_weight(cosine: f64) -> f64 {
+    (1.0 - cosine).clamp(0.0, 1.0).powi(5)
+}
+
+// Every lobe is rough, so directions are sampled from one picked at random and weighted by
+// the sum of them all over the mixed density, which light sampling can be combined with.
+impl Scatterable for Principled {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        rng: &mut SmallRng,
+    ) -> Option<(Ray, Color)> {
+        let axes = frame(hit_record);
+        let wo = to_local(&axes, &-ray.direction.unit_vec());
+        if wo.z <= 0.0 {
+            return None;
+        }
+        let base_color = albedo(&self.base_color, hit_record);
+        let eta = self.eta(hit_record);
+        let probabilities = self.probabilities(&base_color, &wo);
+
+        let u = rng.gen::<f64>();
+        let wi = if u < probabilities[0] {
+            let wi = Vec3::new(0.0, 0.0, 1.0) + Vec3::random_unit_vec(rng);
+            if wi.near_zero() {
+                Vec3::new(0.0, 0.0, 1.0)
+            } else {
+                wi.unit_vec()
+            }
+        } else if u < probabilities[0] + probabilities[1] {
+            let wm = self
+                .distribution
+                .sample_visible(&wo, (rng.gen(), rng.gen()));
+            // reflections below the surface are absorbed, as the lobe's density only covers above
+            Some((-wo).reflect(&wm)).filter(|wi| wi.z > 0.0)?
+        } else if u < probabilities[0] + probabilities[1] + probabilities[2] {
+            sample_rough_dielectric(&self.distribution, &wo, eta, rng)?
         } else {
-            self.index_of_refraction
+            let wm = self
+                .clearcoat_distribution
+                .sample_visible(&wo, (rng.gen(), rng.gen()));
+            Some((-wo).reflect(&wm)).filter(|wi| wi.z > 0.0)?
         };
-        let cos_theta = (-ray.direction.unit_vec()).dot(&hit_record.normal).min(1.0);
-        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
-        let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let direction;
 
-        if cannot_refract || Self::reflectance(cos_theta, refraction_ratio) > rng.gen::<f64>() {
-            direction = ray.direction.unit_vec().reflect(&hit_record.normal);
-        } else {
-            direction = ray
-                .direction
-                .unit_vec()
-                .refract(&hit_record.normal, refraction_ratio);
+        let pdf = self.pdf_local(&probabilities, &wo, &wi, eta);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let value = self.eval_local(&base_color, &wo, &wi, eta);
+        let scattered = Ray::new(hit_record.point, to_world(&axes, &wi), ray.time);
+        Some((scattered, (1.0 / pdf) * value))
+    }
+
+    fn eval(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        let axes = frame(hit_record);
+        let wo = to_local(&axes, &-ray.direction.unit_vec());
+        let wi = to_local(&axes, &direction.unit_vec());
+        let base_color = albedo(&self.base_color, hit_record);
+        self.eval_local(&base_color, &wo, &wi, self.eta(hit_record))
+    }
+
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        let axes = frame(hit_record);
+        let wo = to_local(&axes, &-ray.direction.unit_vec());
+        if wo.z <= 0.0 {
+            return 0.0;
+        }
+        let wi = to_local(&axes, &direction.unit_vec());
+        let base_color = albedo(&self.base_color, hit_record);
+        let probabilities = self.probabilities(&base_color, &wo);
+        self.pdf_local(&probabilities, &wo, &wi, self.eta(hit_record))
+    }
+}
+
+/// Emits light evenly in all directions and does not scatter.
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self { emit }
+    }
+}
+
+impl Scatterable for DiffuseLight {
+    fn scatter(
+        &self,
+        _ray: &Ray,
+        _hit_record: &HitRecord,
+        _rng: &mut SmallRng,
+    ) -> Option<(Ray, Color)> {
+        None
+    }
+
+    fn emitted(&self, _hit_record: &HitRecord) -> Color {
+        self.emit
+    }
+}
+
+/// Another material on a surface with detail from a normal or height map.
+/// The hit is shaded with the normal from `Material::shading_normal` before it is scattered.
+pub struct Bumped {
+    material: Box<Material>,
+    bump_map: BumpMap,
+}
+
+impl Bumped {
+    pub fn new(material: Material, bump_map: BumpMap) -> Self {
+        Self {
+            material: Box::new(material),
+            bump_map,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    /// Reflectance head on, the same for either polarization.
+    fn normal_incidence(eta: f64) -> f64 {
+        ((eta - 1.0) / (eta + 1.0)).powi(2)
+    }
+
+    #[test]
+    fn conductors_without_absorption_reflect_like_dielectrics_head_on() {
+        for eta in [0.2, 1.5, 2.4] {
+            assert!((fresnel_conductor(1.0, eta, 0.0) - normal_incidence(eta)).abs() < 1.0e-12);
+        }
+    }
+
+    #[test]
+    fn dielectrics_reflect_little_head_on_and_all_past_the_critical_angle() {
+        for eta in [1.0 / 1.5, 1.33, 2.4] {
+            assert!((fresnel_dielectric(1.0, eta) - normal_incidence(eta)).abs() < 1.0e-12);
+        }
+        let critical = (1.0 - (1.0 / 1.5f64).powi(2)).sqrt();
+        assert!(fresnel_dielectric(critical + 1.0e-3, 1.0 / 1.5) < 1.0);
+        assert_eq!(fresnel_dielectric(critical - 1.0e-3, 1.0 / 1.5), 1.0);
+        assert_eq!(fresnel_dielectric(0.0, 1.0 / 1.5), 1.0);
+    }
+
+    #[test]
+    fn bk7_has_its_catalogue_index() {
+        // at the helium d line
+        assert!((Dielectric::Bk7.index().at(587.6) - 1.5168).abs() < 1.0e-4);
+    }
+
+    /// Principled materials with each lobe turned up in turn.
+    fn principled_materials() -> Vec<Principled> {
+        let base = || Principled::textured(Arc::new(SolidColor::new(Color::new(1.0, 1.0, 1.0))));
+        vec![
+            base(),
+            base().with_metallic(1.0).with_roughness(0.7),
+            base()
+                .with_sheen(1.0)
+                .with_subsurface(0.5)
+                .with_roughness(0.9),
+            base().with_clearcoat(1.0, 0.2).with_roughness(0.3),
+            base().with_transmission(1.0, 1.5).with_roughness(0.4),
+            base().with_transmission(0.5, 1.5).with_metallic(0.3),
+        ]
+    }
+
+    /// Ray hitting the plane z = 0 from above, `cosine` away from its normal.
+    fn incoming(cosine: f64) -> Ray {
+        let sine = (1.0 - cosine * cosine).sqrt();
+        Ray::new(
+            Vec3::new(sine, 0.0, cosine),
+            Vec3::new(-sine, 0.0, -cosine),
+            0.0,
+        )
+    }
+
+    fn hit<'a>(ray: &Ray, material: &'a Material) -> HitRecord<'a> {
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        HitRecord::new(1.0, ray, origin, Vec3::new(0.0, 0.0, 1.0), material)
+    }
+
+    #[test]
+    fn principled_lobe_weights_split_between_dielectric_and_metal() {
+        let base = || Principled::textured(Arc::new(SolidColor::new(Color::new(1.0, 1.0, 1.0))));
+        for (metallic, transmission) in [(0.0, 0.0), (0.3, 0.5), (1.0, 0.7), (0.0, 1.0)] {
+            let principled = base()
+                .with_metallic(metallic)
+                .with_transmission(transmission, 1.5);
+            let [diffuse, specular, glass, _] = principled.weights();
+            assert!((diffuse + glass - (1.0 - metallic)).abs() < 1.0e-12);
+            assert!((specular + glass - 1.0).abs() < 1.0e-12);
+            let probabilities =
+                principled.probabilities(&Color::new(0.5, 0.5, 0.5), &Vec3::new(0.0, 0.0, 1.0));
+            assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1.0e-12);
+        }
+    }
+
+    #[test]
+    fn principled_reflection_and_transmission_lose_light() {
+        // Disney's diffuse and sheen are not energy conserving at grazing angles, so are left out
+        let black = || Principled::textured(Arc::new(SolidColor::new(Color::new(0.0, 0.0, 0.0))));
+        let white = || Principled::textured(Arc::new(SolidColor::new(Color::new(1.0, 1.0, 1.0))));
+        let materials = [
+            white().with_metallic(1.0).with_roughness(0.3),
+            white().with_metallic(1.0).with_roughness(0.7),
+            black().with_clearcoat(1.0, 0.2),
+            white().with_transmission(1.0, 1.5).with_roughness(0.4),
+            white().with_transmission(1.0, 1.5).with_metallic(0.5),
+        ];
+        let mut rng = SmallRng::seed_from_u64(1);
+        for principled in materials {
+            let material = Material::Principled(principled);
+            for cosine in [0.2, 0.6, 1.0] {
+                let ray = incoming(cosine);
+                let hit = hit(&ray, &material);
+                let count = 20_000;
+                let mut sum = Color::new(0.0, 0.0, 0.0);
+                for _ in 0..count {
+                    if let Some((_, weight)) = material.scatter(&ray, &hit, &mut rng) {
+                        sum += weight;
+                    }
+                }
+                let albedo = sum.avg_by_samples(count).luminance();
+                assert!(albedo < 1.01, "{}", albedo);
+            }
+        }
+    }
+
+    #[test]
+    fn principled_samples_match_its_pdf() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        for principled in principled_materials() {
+            let material = Material::Principled(principled);
+            for cosine in [0.2, 0.6, 1.0] {
+                let ray = incoming(cosine);
+                let hit = hit(&ray, &material);
+
+                // each sample is weighted by the sum of the lobes over the mixed density
+                let count = 20_000;
+                let mut sampled = 0;
+                for _ in 0..count {
+                    let Some((scattered, weight)) = material.scatter(&ray, &hit, &mut rng) else {
+                        continue;
+                    };
+                    sampled += 1;
+                    let pdf = material.pdf(&ray, &hit, &scattered.direction);
+                    let expected = (1.0 / pdf) * material.eval(&ray, &hit, &scattered.direction);
+                    assert!((weight.luminance() - expected.luminance()).abs() < 1.0e-9);
+                }
+
+                // the density covers exactly the samples that are not absorbed, integrated
+                // by angle from the normal to resolve the lobes peaking around it
+                let steps = 500;
+                let (d_theta, d_phi) = (PI / steps as f64, 2.0 * PI / steps as f64);
+                let mut total = 0.0;
+                for i in 0..steps {
+                    let (sin, cos) = ((i as f64 + 0.5) * d_theta).sin_cos();
+                    for j in 0..steps {
+                        let phi = (j as f64 + 0.5) * d_phi;
+                        let direction = Vec3::new(sin * phi.cos(), sin * phi.sin(), cos);
+                        total += material.pdf(&ray, &hit, &direction) * sin * d_theta * d_phi;
+                    }
+                }
+                let fraction = sampled as f64 / count as f64;
+                assert!((total - fraction).abs() < 1.0e-2, "{} {}", total, fraction);
+            }
         }
-        let scattered = Ray::new(hit_record.point, direction);
-        Some((scattered, Color::new(1.0, 1.0, 1.0)))
     }
 }
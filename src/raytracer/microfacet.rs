@@ -0,0 +1,142 @@
+use std::f64::consts::PI;
+
+use super::vec3::*;
+
+/// Below this roughness surfaces are treated as perfectly smooth mirrors.
+const SMOOTH: f64 = 1.0e-3;
+
+/// GGX (Trowbridge-Reitz) distribution of microfacet normals with Smith masking-shadowing.
+/// Directions are in a local frame with the surface normal along z and `alpha_x` along x.
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    /// Alphas are the slopes' spread in each direction, isotropic when they are equal.
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        Self {
+            alpha_x: alpha_x.max(0.0),
+            alpha_y: alpha_y.max(0.0),
+        }
+    }
+
+    /// Alpha for a perceptual roughness in [0, 1], which looks about linear across its range.
+    pub fn roughness_to_alpha(roughness: f64) -> f64 {
+        let roughness = roughness.clamp(0.0, 1.0);
+        roughness * roughness
+    }
+
+    /// Too smooth to sample, directions are only ever reflected or refracted exactly.
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < SMOOTH
+    }
+
+    /// Density of microfacets facing `wm`, per unit of projected area.
+    pub fn d(&self, wm: &Vec3) -> f64 {
+        if wm.z <= 0.0 {
+            return 0.0;
+        }
+        let e = (wm.x / self.alpha_x).powi(2) + (wm.y / self.alpha_y).powi(2) + wm.z * wm.z;
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    /// Smith's auxiliary function, the area of microfacets hidden from `w` per visible area.
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let cos2 = w.z * w.z;
+        if cos2 == 0.0 {
+            return f64::INFINITY;
+        }
+        let alpha2_tan2 = ((self.alpha_x * w.x).powi(2) + (self.alpha_y * w.y).powi(2)) / cos2;
+        0.5 * ((1.0 + alpha2_tan2).sqrt() - 1.0)
+    }
+
+    /// Fraction of microfacets visible from `w`.
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Fraction of microfacets visible from both directions, correlated by height.
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Density of `sample_visible` picking `wm` when seen from `w`, none for normals facing away.
+    pub fn pdf(&self, w: &Vec3, wm: &Vec3) -> f64 {
+        let cosine = w.dot(wm) * w.z.signum();
+        if w.z == 0.0 || cosine <= 0.0 {
+            return 0.0;
+        }
+        self.g1(w) / w.z.abs() * self.d(wm) * cosine
+    }
+
+    /// Microfacet normal seen from `w` (above the surface), picked in proportion to its
+    /// projected area, from `u` uniform in [0, 1)². Only visible normals are sampled, so far
+    /// fewer directions end up below the surface than when sampling `d` alone.
+    pub fn sample_visible(&self, w: &Vec3, u: (f64, f64)) -> Vec3 {
+        // stretch to the hemisphere configuration, where visible normals are a projected disk
+        let wh = vec3!(self.alpha_x * w.x, self.alpha_y * w.y, w.z).unit_vec();
+        let wh = if wh.z < 0.0 { -wh } else { wh };
+        let t1 = if wh.z < 0.99999 {
+            vec3!(0.0, 0.0, 1.0).cross(&wh).unit_vec()
+        } else {
+            vec3!(1.0, 0.0, 0.0)
+        };
+        let t2 = wh.cross(&t1);
+
+        let r = u.0.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let (px, py) = (r * phi.cos(), r * phi.sin());
+        // squash the half of the disk hidden behind the surface
+        let h = (1.0 - px * px).sqrt();
+        let s = 0.5 * (1.0 + wh.z);
+        let py = (1.0 - s) * h + s * py;
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+        let nh = px * t1 + py * t2 + pz * wh;
+
+        vec3!(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(1.0e-6)).unit_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    /// Midpoint rule over the hemisphere, by the cosine and azimuth of each direction.
+    fn integrate(f: impl Fn(&Vec3) -> f64) -> f64 {
+        let steps = 1000;
+        let cell = 1.0 / steps as f64 * 2.0 * PI / steps as f64;
+        let mut sum = 0.0;
+        for i in 0..steps {
+            let cos = (i as f64 + 0.5) / steps as f64;
+            let sin = (1.0 - cos * cos).sqrt();
+            for j in 0..steps {
+                let phi = 2.0 * PI * (j as f64 + 0.5) / steps as f64;
+                sum += f(&vec3!(sin * phi.cos(), sin * phi.sin(), cos)) * cell;
+            }
+        }
+        sum
+    }
+
+    #[test]
+    fn visible_normal_pdf_integrates_to_one_and_matches_samples() {
+        let distribution = TrowbridgeReitz::new(0.3, 0.6);
+        let mut rng = SmallRng::seed_from_u64(1);
+        for w in [vec3!(0.0, 0.0, 1.0), vec3!(0.6, -0.3, 0.4).unit_vec()] {
+            let total = integrate(|wm| distribution.pdf(&w, wm));
+            assert!((total - 1.0).abs() < 1.0e-2, "{}", total);
+
+            // the mean height of sampled normals agrees with the pdf's
+            let expected = integrate(|wm| wm.z * distribution.pdf(&w, wm));
+            let count = 100_000;
+            let mean = (0..count)
+                .map(|_| distribution.sample_visible(&w, (rng.gen(), rng.gen())).z)
+                .sum::<f64>()
+                / count as f64;
+            assert!((mean - expected).abs() < 1.0e-2, "{} {}", mean, expected);
+        }
+    }
+}
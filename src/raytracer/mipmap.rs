@@ -0,0 +1,167 @@
+use super::color::Color;
+use super::input::Image;
+
+/// Ellipses longer than this many times their width are widened, to bound the texels visited.
+const MAX_ANISOTROPY: f64 = 8.0;
+
+/// Image with successively halved copies, for looking up areas of it quickly.
+/// Coordinates are in [0, 1) across the image from the top left and repeat outside of it.
+pub struct MipMap {
+    levels: Vec<Image>,
+}
+
+impl MipMap {
+    /// Halves the image with a box filter until it is a single texel wide and high.
+    pub fn new(image: Image) -> Self {
+        // loading rejects empty images, there would be nothing to look up
+        assert!(!image.pixels.is_empty(), "mipmap of an empty image");
+        let mut levels = vec![image];
+        loop {
+            let last = levels.last().unwrap();
+            if last.width <= 1 && last.height <= 1 {
+                break;
+            }
+            let (width, height) = ((last.width / 2).max(1), (last.height / 2).max(1));
+            let mut pixels = Vec::with_capacity(width * height);
+            for y in 0..height {
+                for x in 0..width {
+                    let texel = |dx: usize, dy: usize| {
+                        last.pixel((2 * x + dx) % last.width, (2 * y + dy) % last.height)
+                    };
+                    pixels.push(0.25 * (texel(0, 0) + texel(1, 0) + texel(0, 1) + texel(1, 1)));
+                }
+            }
+            levels.push(Image {
+                width,
+                height,
+                pixels,
+            });
+        }
+        Self { levels }
+    }
+
+    /// Average over the ellipse with axes `dst0` and `dst1` around `(s, t)`, e.g. a pixel's footprint.
+    /// Elliptically weighted averaging is done in the two levels whose texels are closest
+    /// in size to the ellipse's minor axis, and blended between them.
+    pub fn filter(&self, s: f64, t: f64, dst0: (f64, f64), dst1: (f64, f64)) -> Color {
+        let length = |d: (f64, f64)| (d.0 * d.0 + d.1 * d.1).sqrt();
+        let (mut major_axis, mut minor_axis) = (dst0, dst1);
+        if length(major_axis) < length(minor_axis) {
+            std::mem::swap(&mut major_axis, &mut minor_axis);
+        }
+        let major = length(major_axis);
+        let mut minor = length(minor_axis);
+        if minor * MAX_ANISOTROPY < major && minor > 0.0 {
+            let scale = major / (minor * MAX_ANISOTROPY);
+            minor_axis = (scale * minor_axis.0, scale * minor_axis.1);
+            minor *= scale;
+        }
+        if minor == 0.0 {
+            return self.bilinear(0, s, t);
+        }
+
+        let size = self.levels[0].width.max(self.levels[0].height) as f64;
+        let lod = (size * minor)
+            .log2()
+            .clamp(0.0, (self.levels.len() - 1) as f64);
+        let level = lod.floor() as usize;
+        let fraction = lod - level as f64;
+        let fine = self.ewa(level, s, t, major_axis, minor_axis);
+        if fraction == 0.0 {
+            return fine;
+        }
+        let coarse = self.ewa(level + 1, s, t, major_axis, minor_axis);
+        (1.0 - fraction) * fine + fraction * coarse
+    }
+
+    /// Interpolates the four texels around `(s, t)` in one level.
+    pub fn bilinear(&self, level: usize, s: f64, t: f64) -> Color {
+        let image = &self.levels[level.min(self.levels.len() - 1)];
+        let x = s * image.width as f64 - 0.5;
+        let y = t * image.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = (1.0 - fx) * texel(image, x0, y0) + fx * texel(image, x0 + 1, y0);
+        let bottom = (1.0 - fx) * texel(image, x0, y0 + 1) + fx * texel(image, x0 + 1, y0 + 1);
+        (1.0 - fy) * top + fy * bottom
+    }
+
+    /// Gaussian weighted average of the texels inside the ellipse.
+    fn ewa(&self, level: usize, s: f64, t: f64, dst0: (f64, f64), dst1: (f64, f64)) -> Color {
+        let image = &self.levels[level.min(self.levels.len() - 1)];
+        let (width, height) = (image.width as f64, image.height as f64);
+        let (x, y) = (s * width - 0.5, t * height - 0.5);
+        let (dst0, dst1) = (
+            (dst0.0 * width, dst0.1 * height),
+            (dst1.0 * width, dst1.1 * height),
+        );
+
+        // implicit ellipse A s² + B s t + C t² = 1, grown by a texel so it covers at least one
+        let mut a = dst0.1 * dst0.1 + dst1.1 * dst1.1 + 1.0;
+        let mut b = -2.0 * (dst0.0 * dst0.1 + dst1.0 * dst1.1);
+        let mut c = dst0.0 * dst0.0 + dst1.0 * dst1.0 + 1.0;
+        let inverse_f = 1.0 / (a * c - b * b * 0.25);
+        a *= inverse_f;
+        b *= inverse_f;
+        c *= inverse_f;
+
+        let det = 4.0 * a * c - b * b;
+        let (s_extent, t_extent) = (2.0 * (det * c).sqrt() / det, 2.0 * (a * det).sqrt() / det);
+        let (x0, x1) = ((x - s_extent).ceil() as i64, (x + s_extent).floor() as i64);
+        let (y0, y1) = ((y - t_extent).ceil() as i64, (y + t_extent).floor() as i64);
+
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        let mut weights = 0.0;
+        for ty in y0..=y1 {
+            let dy = ty as f64 - y;
+            for tx in x0..=x1 {
+                let dx = tx as f64 - x;
+                let r2 = a * dx * dx + b * dx * dy + c * dy * dy;
+                if r2 < 1.0 {
+                    let weight = (-2.0 * r2).exp() - (-2.0f64).exp();
+                    sum += weight * texel(image, tx, ty);
+                    weights += weight;
+                }
+            }
+        }
+        if weights > 0.0 {
+            (1.0 / weights) * sum
+        } else {
+            self.bilinear(level, s, t)
+        }
+    }
+}
+
+fn texel(image: &Image, x: i64, y: i64) -> Color {
+    image.pixel(
+        x.rem_euclid(image.width as i64) as usize,
+        y.rem_euclid(image.height as i64) as usize,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_halve_down_to_the_mean() {
+        let pixels = (0..8).map(|i| Color::new(i as f64, 1.0, 0.0)).collect();
+        let mipmap = MipMap::new(Image {
+            width: 4,
+            height: 2,
+            pixels,
+        });
+        let sizes: Vec<(usize, usize)> = mipmap
+            .levels
+            .iter()
+            .map(|level| (level.width, level.height))
+            .collect();
+        assert_eq!(sizes, [(4, 2), (2, 1), (1, 1)]);
+        let half = &mipmap.levels[1];
+        assert_eq!((half.pixel(0, 0).r, half.pixel(1, 0).r), (2.5, 4.5));
+        let mean = mipmap.levels[2].pixel(0, 0);
+        assert_eq!((mean.r, mean.g), (3.5, 1.0));
+    }
+}
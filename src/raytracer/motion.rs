@@ -0,0 +1,60 @@
+use super::aabb::Aabb;
+use super::vec3::Vec3;
+
+/// Position over time, moving linearly between keyframes and resting before the first and after the last.
+#[derive(Clone)]
+pub struct Motion {
+    /// Sorted by time
+    keyframes: Vec<(f64, Vec3)>,
+}
+
+impl Motion {
+    pub fn fixed(position: Vec3) -> Self {
+        Self {
+            keyframes: vec![(0.0, position)],
+        }
+    }
+
+    /// From `start` at `time0` to `end` at `time1`.
+    pub fn linear(time0: f64, start: Vec3, time1: f64, end: Vec3) -> Self {
+        Self {
+            keyframes: vec![(time0, start), (time1, end)],
+        }
+    }
+
+    /// Keyframes are `(time, position)` pairs in increasing time order.
+    pub fn keyframed(keyframes: Vec<(f64, Vec3)>) -> Result<Self, String> {
+        if keyframes.is_empty() {
+            return Err("needs at least one keyframe".to_string());
+        }
+        if keyframes.windows(2).any(|k| k[0].0 >= k[1].0) {
+            return Err("keyframe times must increase".to_string());
+        }
+        Ok(Self { keyframes })
+    }
+
+    pub fn at(&self, time: f64) -> Vec3 {
+        let keyframes = &self.keyframes;
+        let next = keyframes.partition_point(|&(t, _)| t <= time);
+        if next == 0 {
+            return keyframes[0].1;
+        }
+        if next == keyframes.len() {
+            return keyframes[next - 1].1;
+        }
+        let (t0, p0) = keyframes[next - 1];
+        let (t1, p1) = keyframes[next];
+        let a = (time - t0) / (t1 - t0);
+        (1.0 - a) * p0 + a * p1
+    }
+
+    /// Box around every position, the keyframes span all of them.
+    pub fn bounds(&self) -> Aabb {
+        let (_, first) = self.keyframes[0];
+        self.keyframes
+            .iter()
+            .fold(Aabb::from_points(first, first), |bbox, &(_, p)| {
+                Aabb::enclosing(&bbox, &Aabb::from_points(p, p))
+            })
+    }
+}
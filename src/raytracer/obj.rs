@@ -0,0 +1,357 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::bump::BumpMap;
+use super::color::Color;
+use super::input;
+use super::materials::*;
+use super::texture::{ImageTexture, SolidColor, Texture};
+use super::triangle::{MeshError, TriangleMesh};
+use super::vec3::Vec3;
+
+/// Error loading an OBJ file, naming the part of it at fault.
+pub enum ObjError {
+    Obj(tobj::LoadError),
+    Mtl(tobj::LoadError),
+    /// A texture map of the named material
+    Texture {
+        material: String,
+        message: String,
+    },
+    /// The faces of the named group
+    Mesh {
+        model: String,
+        error: MeshError,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Obj(e) => write!(f, "{}", e),
+            ObjError::Mtl(e) => write!(f, "materials: {}", e),
+            ObjError::Texture { material, message } => {
+                write!(f, "material `{}`: {}", material, message)
+            }
+            ObjError::Mesh { model, error } => write!(f, "group `{}`: {}", model, error),
+        }
+    }
+}
+
+/// Loads a Wavefront OBJ file as one mesh per group and `usemtl` section.
+/// Materials come from the referenced MTL files unless `material` replaces them all.
+pub fn load(path: &Path, material: Option<Arc<Material>>) -> Result<Vec<TriangleMesh>, ObjError> {
+    let options = tobj::LoadOptions {
+        triangulate: true,
+        single_index: true,
+        ..Default::default()
+    };
+    let (models, mtl) = tobj::load_obj(path, &options).map_err(ObjError::Obj)?;
+
+    let materials: Vec<Arc<Material>> = match &material {
+        Some(_) => Vec::new(),
+        None => {
+            let base = path.parent().unwrap_or(Path::new(""));
+            mtl.map_err(ObjError::Mtl)?
+                .iter()
+                .map(|m| from_mtl(m, base).map(Arc::new))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    let default_material = material.unwrap_or_else(|| {
+        Arc::new(Material::Lambertian(Lambertian::new(Color::new(
+            0.8, 0.8, 0.8,
+        ))))
+    });
+
+    let mut meshes = Vec::new();
+    for model in models {
+        let mesh = model.mesh;
+        if mesh.indices.is_empty() {
+            continue;
+        }
+        let material = mesh
+            .material_id
+            .and_then(|id| materials.get(id))
+            .unwrap_or(&default_material)
+            .clone();
+
+        let triangle_mesh = TriangleMesh::new(
+            mesh.positions
+                .chunks(3)
+                .map(|p| Vec3::new(p[0] as f64, p[1] as f64, p[2] as f64))
+                .collect(),
+            mesh.normals
+                .chunks(3)
+                .map(|n| Vec3::new(n[0] as f64, n[1] as f64, n[2] as f64).unit_vec())
+                .collect(),
+            mesh.texcoords
+                .chunks(2)
+                .map(|uv| (uv[0] as f64, uv[1] as f64))
+                .collect(),
+            mesh.indices
+                .chunks(3)
+                .map(|i| [i[0] as usize, i[1] as usize, i[2] as usize])
+                .collect(),
+            material,
+        )
+        .map_err(|error| ObjError::Mesh {
+            model: model.name.clone(),
+            error,
+        })?;
+        meshes.push(triangle_mesh);
+    }
+    Ok(meshes)
+}
+
+/// Maps an MTL material onto the closest of our materials: emissive ones become lights,
+/// ones using the PBR extension (`Pr`, `Pm`, `Ps`, `Pc`) principled, transparent ones glass,
+/// mirror-like ones metal and everything else diffuse.
+/// A diffuse texture map, relative to `base`, replaces the diffuse color, and a bump or normal map
+/// adds detail to any of them.
+pub fn from_mtl(mtl: &tobj::Material, base: &Path) -> Result<Material, ObjError> {
+    let material = base_material(mtl, base)?;
+    let bump_map = if let Some(map) = &mtl.normal_texture {
+        let (file, options) = texture_options(map);
+        let scale = options
+            .windows(2)
+            .find(|o| o[0] == "-bm")
+            .and_then(|o| o[1].parse().ok())
+            .unwrap_or(1.0);
+        Some(BumpMap::Height {
+            texture: data_texture(base, file, &mtl.name)?,
+            scale,
+        })
+    } else if let Some(map) = mtl.unknown_param.get("norm") {
+        let (file, _) = texture_options(map);
+        Some(BumpMap::Normal(data_texture(base, file, &mtl.name)?))
+    } else {
+        None
+    };
+    Ok(match bump_map {
+        Some(bump_map) => Material::Bumped(Bumped::new(material, bump_map)),
+        None => material,
+    })
+}
+
+/// The file name at the end of a texture map statement and the options before it.
+fn texture_options(map: &str) -> (&str, Vec<&str>) {
+    let mut words: Vec<&str> = map.split_whitespace().collect();
+    let file = words.pop().unwrap_or("");
+    (file, words)
+}
+
+fn texture_error(material: &str) -> impl FnOnce(String) -> ObjError + '_ {
+    move |message| ObjError::Texture {
+        material: material.to_string(),
+        message,
+    }
+}
+
+fn data_texture(base: &Path, file: &str, material: &str) -> Result<Arc<ImageTexture>, ObjError> {
+    let image = input::load_data(&base.join(file)).map_err(texture_error(material))?;
+    Ok(Arc::new(ImageTexture::new(image)))
+}
+
+/// The material before a bump or normal map is added.
+fn base_material(mtl: &tobj::Material, base: &Path) -> Result<Material, ObjError> {
+    let color = |c: [f32; 3]| Color::new(c[0] as f64, c[1] as f64, c[2] as f64);
+    let kd = mtl.diffuse.map_or(Color::new(0.8, 0.8, 0.8), color);
+    let ks = mtl.specular.map_or(Color::new(0.0, 0.0, 0.0), color);
+    let ns = mtl.shininess.unwrap_or(0.0) as f64;
+    let ni = mtl.optical_density.unwrap_or(1.5) as f64;
+    let dissolve = mtl.dissolve.unwrap_or(1.0);
+    let illum = mtl.illumination_model.unwrap_or(2);
+    let ke = mtl.emissive.map_or(Color::new(0.0, 0.0, 0.0), color);
+
+    let pbr = |key: &str| -> Option<f64> {
+        mtl.unknown_param
+            .get(key)
+            .and_then(|v| v.split_whitespace().next()?.parse().ok())
+    };
+    let diffuse = || -> Result<Arc<dyn Texture>, ObjError> {
+        Ok(match &mtl.diffuse_texture {
+            Some(texture) => {
+                let (file, _) = texture_options(texture);
+                let image = input::load(&base.join(file)).map_err(texture_error(&mtl.name))?;
+                Arc::new(ImageTexture::new(image))
+            }
+            None => Arc::new(SolidColor::new(kd)),
+        })
+    };
+
+    let max = |c: &Color| c.r.max(c.g).max(c.b);
+    Ok(if max(&ke) > 0.0 {
+        Material::DiffuseLight(DiffuseLight::new(ke))
+    } else if ["Pr", "Pm", "Ps", "Pc"]
+        .iter()
+        .any(|key| pbr(key).is_some())
+    {
+        Material::Principled(
+            Principled::textured(diffuse()?)
+                .with_roughness(pbr("Pr").unwrap_or(0.5))
+                .with_metallic(pbr("Pm").unwrap_or(0.0))
+                .with_sheen(pbr("Ps").unwrap_or(0.0))
+                .with_clearcoat(pbr("Pc").unwrap_or(0.0), pbr("Pcr").unwrap_or(0.1))
+                .with_transmission(1.0 - dissolve as f64, ni),
+        )
+    } else if dissolve < 1.0 || matches!(illum, 4 | 6 | 7 | 9) {
+        // the transmission filter is taken as the color left after one unit inside,
+        // exporters write a black one for no filter at all
+        let tf: Vec<f64> = mtl
+            .unknown_param
+            .get("Tf")
+            .map(|tf| {
+                tf.split_whitespace()
+                    .map_while(|c| c.parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        match tf[..] {
+            [r, g, b] if r.max(g).max(b) > 0.0 => {
+                Material::Glass(Glass::new(ni).with_absorption(Color::new(r, g, b), 1.0))
+            }
+            _ => Material::Glass(Glass::new(ni)),
+        }
+    } else if illum == 3 || (illum != 1 && max(&ks) > max(&kd)) {
+        Material::Metal(Metal::new(ks, phong_roughness(ns)))
+    } else {
+        Material::Lambertian(Lambertian::textured(diffuse()?))
+    })
+}
+
+/// Roughness giving the GGX alpha that matches a Phong exponent, a sharp highlight is a clean mirror.
+fn phong_roughness(exponent: f64) -> f64 {
+    (2.0 / (exponent + 2.0)).sqrt().sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::raytracer::aabb::Aabb;
+    use crate::raytracer::interval::Interval;
+    use crate::raytracer::microfacet::TrowbridgeReitz;
+    use crate::raytracer::ray::{HitRecord, Hittable, Ray};
+
+    fn mtl(diffuse: [f32; 3], specular: [f32; 3], params: &[(&str, &str)]) -> tobj::Material {
+        tobj::Material {
+            name: "test".to_string(),
+            diffuse: Some(diffuse),
+            specular: Some(specular),
+            unknown_param: params
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn material(mtl: tobj::Material) -> Material {
+        from_mtl(&mtl, Path::new("")).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    #[test]
+    fn emission_makes_a_light() {
+        let light = material(tobj::Material {
+            emissive: Some([4.0, 4.0, 4.0]),
+            ..mtl([0.8, 0.8, 0.8], [0.0, 0.0, 0.0], &[])
+        });
+        assert!(matches!(light, Material::DiffuseLight(_)));
+    }
+
+    #[test]
+    fn pbr_parameters_make_a_principled_material() {
+        for key in ["Pr", "Pm"] {
+            let principled = material(mtl([0.8, 0.8, 0.8], [0.0, 0.0, 0.0], &[(key, "0.3")]));
+            assert!(matches!(principled, Material::Principled(_)), "{}", key);
+        }
+    }
+
+    #[test]
+    fn transparency_makes_glass_tinted_by_the_filter() {
+        let dissolved = tobj::Material {
+            dissolve: Some(0.5),
+            ..mtl([0.8, 0.8, 0.8], [0.0, 0.0, 0.0], &[])
+        };
+        assert!(matches!(material(dissolved), Material::Glass(_)));
+        for illum in [4, 6, 7, 9] {
+            let glass = tobj::Material {
+                illumination_model: Some(illum),
+                ..mtl([0.8, 0.8, 0.8], [0.0, 0.0, 0.0], &[])
+            };
+            assert!(matches!(material(glass), Material::Glass(_)), "{}", illum);
+        }
+
+        // what is left of the light after one unit inside
+        let tinted = material(tobj::Material {
+            dissolve: Some(0.0),
+            ..mtl([0.8, 0.8, 0.8], [0.0, 0.0, 0.0], &[("Tf", "0.5 0.8 1.0")])
+        });
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let inside = HitRecord::new(1.0, &ray, Vec3::new(0.0, 0.0, 0.0), ray.direction, &tinted);
+        let (_, attenuation) = tinted
+            .scatter(&ray, &inside, &mut SmallRng::seed_from_u64(1))
+            .unwrap();
+        assert!((attenuation.r - 0.5).abs() < 1.0e-9);
+        assert!((attenuation.g - 0.8).abs() < 1.0e-9);
+        assert!((attenuation.b - 1.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn mirror_illumination_or_strong_specular_makes_metal() {
+        let mirror = tobj::Material {
+            illumination_model: Some(3),
+            ..mtl([0.8, 0.8, 0.8], [0.1, 0.1, 0.1], &[])
+        };
+        assert!(matches!(material(mirror), Material::Metal(_)));
+        let shiny = mtl([0.1, 0.1, 0.1], [0.9, 0.9, 0.9], &[]);
+        assert!(matches!(material(shiny), Material::Metal(_)));
+        let dull = mtl([0.8, 0.8, 0.8], [0.1, 0.1, 0.1], &[]);
+        assert!(matches!(material(dull), Material::Lambertian(_)));
+    }
+
+    #[test]
+    fn phong_exponents_match_ggx_widths() {
+        // the Beckmann width of a Phong lobe, which GGX alpha stands in for
+        for exponent in [0.0, 10.0, 100.0, 1000.0] {
+            let alpha = TrowbridgeReitz::roughness_to_alpha(phong_roughness(exponent));
+            assert!((alpha - (2.0 / (exponent + 2.0)).sqrt()).abs() < 1.0e-12);
+        }
+        assert_eq!(phong_roughness(0.0), 1.0);
+        assert!(phong_roughness(1.0e6) < 0.05);
+    }
+
+    #[test]
+    fn cube_loads_as_a_mesh_per_group() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/cube.obj");
+        let meshes = load(&path, None).unwrap_or_else(|e| panic!("{}", e));
+        let triangles: Vec<usize> = meshes.iter().map(|m| m.triangle_count()).collect();
+        assert_eq!(triangles, [10, 2]);
+
+        let bounds = meshes
+            .iter()
+            .map(|m| m.bounding_box())
+            .reduce(|a, b| Aabb::enclosing(&a, &b))
+            .unwrap();
+        for (axis, (min, max)) in [(-0.5, 0.5), (0.0, 1.0), (-0.5, 0.5)]
+            .into_iter()
+            .enumerate()
+        {
+            let interval = bounds.axis(axis);
+            assert!((interval.min - min).abs() < 1.0e-3 && (interval.max - max).abs() < 1.0e-3);
+        }
+
+        // the top is split into two triangles covering all of it
+        let origin = Vec3::new(0.2, 3.0, 0.1);
+        assert!(meshes[1]
+            .hit(
+                &Ray::new(origin, Vec3::new(0.0, -1.0, 0.0), 0.0),
+                Interval::new(0.001, f64::INFINITY)
+            )
+            .is_some_and(|hit| (hit.t - 2.0).abs() < 1.0e-9));
+    }
+}
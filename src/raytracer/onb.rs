@@ -0,0 +1,27 @@
+use super::vec3::*;
+
+/// Orthonormal basis with `w` along a given direction.
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn new(w: &Vec3) -> Self {
+        let w = w.unit_vec();
+        let a = if w.x.abs() > 0.9 {
+            vec3!(0.0, 1.0, 0.0)
+        } else {
+            vec3!(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(&a).unit_vec();
+        let u = w.cross(&v);
+        Self { u, v, w }
+    }
+
+    /// World space vector of coordinates in this basis.
+    pub fn local(&self, a: &Vec3) -> Vec3 {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+}
@@ -0,0 +1,365 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, Write};
+use std::path::Path;
+
+use exr::prelude::{f16, Image, SpecificChannels, Vec2, WritableImage};
+
+use super::color::Color;
+use super::framebuffer::Framebuffer;
+
+pub enum ImageFormat {
+    /// 8-bit gamma corrected PNG
+    Png,
+    /// 8-bit gamma corrected binary PPM (P6)
+    Ppm,
+    /// 8-bit gamma corrected ASCII PPM (P3), chosen with a `.ascii.ppm` suffix
+    PpmAscii,
+    /// 32-bit float linear radiance PFM
+    Pfm,
+    /// 16-bit half float linear radiance OpenEXR
+    ExrHalf,
+    /// 32-bit float linear radiance OpenEXR, chosen with a `.float.exr` suffix
+    ExrFloat,
+    /// Radiance RGBE linear radiance
+    Hdr,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        let stem = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .map(|s| s.to_ascii_lowercase())
+            .unwrap_or_default();
+        let ascii = stem.ends_with(".ascii");
+        let float = stem.ends_with(".float");
+
+        match extension.as_deref() {
+            Some("png") => Ok(ImageFormat::Png),
+            Some("ppm") if ascii => Ok(ImageFormat::PpmAscii),
+            Some("ppm") => Ok(ImageFormat::Ppm),
+            Some("pfm") => Ok(ImageFormat::Pfm),
+            Some("exr") if float => Ok(ImageFormat::ExrFloat),
+            Some("exr") => Ok(ImageFormat::ExrHalf),
+            Some("hdr") => Ok(ImageFormat::Hdr),
+            _ => Err(format!(
+                "{}: unsupported image format, expected .png, .ppm, .pfm, .exr or .hdr",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// Saves the framebuffer in the format given by the file extension.
+pub fn save(framebuffer: &Framebuffer, path: &Path) -> Result<(), String> {
+    let format = ImageFormat::from_path(path)?;
+    let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut writer = BufWriter::new(file);
+
+    match format {
+        ImageFormat::Png => write_png(framebuffer, &mut writer),
+        ImageFormat::Ppm => write_ppm(framebuffer, &mut writer),
+        ImageFormat::PpmAscii => write_ppm_ascii(framebuffer, &mut writer),
+        ImageFormat::Pfm => write_pfm(framebuffer, &mut writer),
+        ImageFormat::ExrHalf => write_exr(framebuffer, &mut writer, f16::from_f64),
+        ImageFormat::ExrFloat => write_exr(framebuffer, &mut writer, |v| v as f32),
+        ImageFormat::Hdr => write_hdr(framebuffer, &mut writer),
+    }
+    .and_then(|_| writer.flush())
+    .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn write_png(framebuffer: &Framebuffer, writer: impl Write) -> io::Result<()> {
+    let mut encoder =
+        png::Encoder::new(writer, framebuffer.width as u32, framebuffer.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut w| w.write_image_data(&framebuffer.to_rgb8()))
+        .map_err(io::Error::other)
+}
+
+pub fn write_ppm(framebuffer: &Framebuffer, mut writer: impl Write) -> io::Result<()> {
+    write!(
+        writer,
+        "P6\n{} {}\n255\n",
+        framebuffer.width, framebuffer.height
+    )?;
+    writer.write_all(&framebuffer.to_rgb8())
+}
+
+pub fn write_ppm_ascii(framebuffer: &Framebuffer, mut writer: impl Write) -> io::Result<()> {
+    write!(
+        writer,
+        "P3\n{} {}\n255\n",
+        framebuffer.width, framebuffer.height
+    )?;
+    for rgb in framebuffer.to_rgb8().chunks(3) {
+        writeln!(writer, "{} {} {}", rgb[0], rgb[1], rgb[2])?;
+    }
+    Ok(())
+}
+
+/// PFM stores rows bottom to top, a negative scale marks little endian.
+pub fn write_pfm(framebuffer: &Framebuffer, mut writer: impl Write) -> io::Result<()> {
+    write!(
+        writer,
+        "PF\n{} {}\n-1.0\n",
+        framebuffer.width, framebuffer.height
+    )?;
+    for row in framebuffer.pixels.chunks(framebuffer.width).rev() {
+        for c in row {
+            for value in [c.r, c.g, c.b] {
+                writer.write_all(&(value as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes RGB channels as half or float depending on the sample type `convert` produces.
+pub fn write_exr<T>(
+    framebuffer: &Framebuffer,
+    writer: impl Write + Seek,
+    convert: fn(f64) -> T,
+) -> io::Result<()>
+where
+    T: exr::prelude::IntoSample,
+{
+    let channels = SpecificChannels::rgb(|Vec2(x, y): Vec2<usize>| {
+        let c = framebuffer.pixels[y * framebuffer.width + x];
+        (convert(c.r), convert(c.g), convert(c.b))
+    });
+    Image::from_channels((framebuffer.width, framebuffer.height), channels)
+        .write()
+        .to_buffered(writer)
+        .map_err(io::Error::other)
+}
+
+/// Radiance RGBE, a shared 8-bit exponent per pixel. Scanlines are run length encoded when
+/// they are 8 to 32767 pixels wide; flat ones that long could start like an encoded one.
+pub fn write_hdr(framebuffer: &Framebuffer, mut writer: impl Write) -> io::Result<()> {
+    write!(
+        writer,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        framebuffer.height, framebuffer.width
+    )?;
+    let width = framebuffer.width;
+    let encoded = (8..0x8000).contains(&width);
+    for row in framebuffer.pixels.chunks(width.max(1)) {
+        let scanline: Vec<[u8; 4]> = row.iter().map(rgbe).collect();
+        if !encoded {
+            for pixel in &scanline {
+                writer.write_all(pixel)?;
+            }
+            continue;
+        }
+        writer.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        // each component is run length encoded separately
+        for channel in 0..4 {
+            let component: Vec<u8> = scanline.iter().map(|pixel| pixel[channel]).collect();
+            write_hdr_runs(&mut writer, &component)?;
+        }
+    }
+    Ok(())
+}
+
+fn rgbe(c: &Color) -> [u8; 4] {
+    let v = c.r.max(c.g).max(c.b);
+    if v < 1.0e-32 {
+        return [0, 0, 0, 0];
+    }
+    let exponent = v.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f64.powi(exponent);
+    [
+        (c.r * scale) as u8,
+        (c.g * scale) as u8,
+        (c.b * scale) as u8,
+        (exponent + 128) as u8,
+    ]
+}
+
+/// Runs of at least 4 equal bytes as their length plus 128 and the byte, up to 127 long,
+/// and the bytes between them as their count and the bytes themselves, up to 128 at a time.
+fn write_hdr_runs(writer: &mut impl Write, data: &[u8]) -> io::Result<()> {
+    const MIN_RUN: usize = 4;
+    let mut x = 0;
+    while x < data.len() {
+        // find where the next run long enough to be worth encoding starts
+        let mut run_start = x;
+        let mut run = 0;
+        while run_start < data.len() {
+            run = data[run_start..]
+                .iter()
+                .take(127)
+                .take_while(|&&b| b == data[run_start])
+                .count();
+            if run >= MIN_RUN {
+                break;
+            }
+            run_start += run;
+        }
+        while x < run_start {
+            let count = (run_start - x).min(128);
+            writer.write_all(&[count as u8])?;
+            writer.write_all(&data[x..x + count])?;
+            x += count;
+        }
+        if run_start < data.len() {
+            writer.write_all(&[128 + run as u8, data[run_start]])?;
+            x += run;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::raytracer::input;
+
+    fn round_trip_hdr(width: usize, height: usize) {
+        let mut rng = SmallRng::seed_from_u64(3);
+        let mut framebuffer = Framebuffer::new(width, height);
+        for (i, pixel) in framebuffer.pixels.iter_mut().enumerate() {
+            // stretches of equal pixels to be run length encoded between noisy ones
+            *pixel = if i % 7 < 4 {
+                Color::new(0.25, 0.5, 2.0)
+            } else {
+                Color::new(rng.gen(), rng.gen(), 8.0 * rng.gen::<f64>())
+            };
+        }
+        // starts like an encoded scanline, red and green of 2 with blue under 128
+        framebuffer.pixels[0] = Color::new(2.0 / 128.0, 2.0 / 128.0, 0.6);
+
+        let mut data = Vec::new();
+        write_hdr(&framebuffer, &mut data).unwrap();
+        let image = input::read_hdr(&data[..]).unwrap();
+
+        assert_eq!((image.width, image.height), (width, height));
+        for (written, read) in framebuffer.pixels.iter().zip(&image.pixels) {
+            let v = written.r.max(written.g).max(written.b);
+            for (a, b) in [
+                (written.r, read.r),
+                (written.g, read.g),
+                (written.b, read.b),
+            ] {
+                assert!((a - b).abs() <= v / 128.0, "wrote {} read {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn hdr_round_trips_encoded_scanlines() {
+        round_trip_hdr(300, 4);
+    }
+
+    #[test]
+    fn hdr_round_trips_flat_scanlines() {
+        round_trip_hdr(5, 3);
+    }
+
+    /// Distinct values in every channel of every pixel, within [0, 1) unless `range` is larger.
+    fn gradient(width: usize, height: usize, range: f64) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(width, height);
+        let count = framebuffer.pixels.len() as f64;
+        for (i, pixel) in framebuffer.pixels.iter_mut().enumerate() {
+            let t = range * i as f64 / count;
+            *pixel = Color::new(t, 0.5 * t, range - t - 1.0e-3);
+        }
+        framebuffer
+    }
+
+    fn assert_matches(framebuffer: &Framebuffer, image: &input::Image, tolerance: f64) {
+        assert_eq!(
+            (image.width, image.height),
+            (framebuffer.width, framebuffer.height)
+        );
+        for (written, read) in framebuffer.pixels.iter().zip(&image.pixels) {
+            for (a, b) in [
+                (written.r, read.r),
+                (written.g, read.g),
+                (written.b, read.b),
+            ] {
+                assert!(
+                    (a - b).abs() <= tolerance * a.max(1.0),
+                    "wrote {} read {}",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pfm_round_trips() {
+        let framebuffer = gradient(5, 3, 40.0);
+        let mut data = Vec::new();
+        write_pfm(&framebuffer, &mut data).unwrap();
+        assert!(data.starts_with(b"PF\n5 3\n-1.0\n"));
+        assert_matches(&framebuffer, &input::read_pfm(&data[..]).unwrap(), 1.0e-7);
+    }
+
+    #[test]
+    fn exr_round_trips_half_and_float() {
+        let framebuffer = gradient(5, 3, 40.0);
+        for (suffix, tolerance) in [("exr", 1.0e-3), ("float.exr", 1.0e-7)] {
+            let path = std::env::temp_dir().join(format!(
+                "raytracer-output-test-{}.{}",
+                std::process::id(),
+                suffix
+            ));
+            save(&framebuffer, &path).unwrap();
+            let image = input::read_exr(&path);
+            std::fs::remove_file(&path).unwrap();
+            assert_matches(&framebuffer, &image.unwrap(), tolerance);
+        }
+    }
+
+    #[test]
+    fn ppm_writes_gamma_encoded_bytes_from_the_top() {
+        let framebuffer = gradient(3, 2, 1.0);
+        let rgb8 = framebuffer.to_rgb8();
+
+        let mut binary = Vec::new();
+        write_ppm(&framebuffer, &mut binary).unwrap();
+        let header = b"P6\n3 2\n255\n";
+        assert_eq!(&binary[..header.len()], header);
+        assert_eq!(&binary[header.len()..], &rgb8[..]);
+
+        let mut ascii = Vec::new();
+        write_ppm_ascii(&framebuffer, &mut ascii).unwrap();
+        let ascii = String::from_utf8(ascii).unwrap();
+        let body = ascii.strip_prefix("P3\n3 2\n255\n").unwrap();
+        let values: Vec<u8> = body
+            .split_whitespace()
+            .map(|v| v.parse().unwrap())
+            .collect();
+        assert_eq!(values, rgb8);
+        assert_eq!(body.lines().count(), 6);
+    }
+
+    #[test]
+    fn png_round_trips_gamma_encoded() {
+        let framebuffer = gradient(4, 3, 1.0);
+        let mut data = Vec::new();
+        write_png(&framebuffer, &mut data).unwrap();
+        let image = input::read_png(&data[..], false).unwrap();
+        assert_eq!((image.width, image.height), (4, 3));
+        let read: Vec<u8> = image
+            .pixels
+            .iter()
+            .flat_map(|c| [c.r, c.g, c.b])
+            .map(|v| (v * 255.0).round() as u8)
+            .collect();
+        assert_eq!(read, framebuffer.to_rgb8());
+    }
+}
@@ -1,15 +1,52 @@
+use std::sync::Arc;
+
+use rand::rngs::SmallRng;
+
+use super::aabb::{self, Aabb};
 use super::interval::Interval;
 use super::materials::Material;
-use super::vec3::Vec3;
+use super::onb::Onb;
+use super::vec3::*;
 
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
+    /// When the ray was sent, for objects that move while the shutter is open
+    pub time: f64,
+    /// How the ray changes between neighbouring pixels, for filtering textures
+    pub differential: Option<Differential>,
+    /// Hero wavelength of the path in nanometers when rendering spectrally
+    pub wavelength: Option<f64>,
+}
+
+/// Rays offset by about one pixel horizontally and vertically.
+#[derive(Clone, Copy)]
+pub struct Differential {
+    pub rx_origin: Vec3,
+    pub rx_direction: Vec3,
+    pub ry_origin: Vec3,
+    pub ry_direction: Vec3,
 }
 
 impl Ray {
-    pub fn new(origin: Vec3, direction: Vec3) -> Self {
-        Self { origin, direction }
+    pub fn new(origin: Vec3, direction: Vec3, time: f64) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+            differential: None,
+            wavelength: None,
+        }
+    }
+
+    pub fn with_differential(mut self, differential: Option<Differential>) -> Self {
+        self.differential = differential;
+        self
+    }
+
+    pub fn with_wavelength(mut self, wavelength: Option<f64>) -> Self {
+        self.wavelength = wavelength;
+        self
     }
 
     pub fn at(&self, t: f64) -> Vec3 {
@@ -17,34 +54,251 @@ impl Ray {
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct HitRecord<'a> {
     pub material: &'a Material,
     pub t: f64,
     pub point: Vec3,
     pub normal: Vec3,
+    /// Normal of the actual surface, which `normal` may differ from for shading
+    pub geometric_normal: Vec3,
     pub front_face: bool,
+    /// Surface coordinates, e.g. for texture lookups
+    pub u: f64,
+    pub v: f64,
+    /// How the point moves with the surface coordinates, zero if they are not mapped
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    /// How the unflipped shading normal turns with the surface coordinates, zero for flat surfaces
+    pub dndu: Vec3,
+    pub dndv: Vec3,
+    /// How the point and surface coordinates change between neighbouring pixels,
+    /// zero unless the ray had differentials
+    pub dpdx: Vec3,
+    pub dpdy: Vec3,
+    pub duvdx: (f64, f64),
+    pub duvdy: (f64, f64),
 }
 
 impl<'a> HitRecord<'a> {
     /// out_normal needs to be a unit vector
     pub fn new(t: f64, ray: &Ray, point: Vec3, out_normal: Vec3, material: &'a Material) -> Self {
         let front_face = ray.direction.dot(&out_normal) < 0.0;
+        let normal = if front_face { out_normal } else { -out_normal };
         Self {
             t,
             point,
-            normal: if front_face { out_normal } else { -out_normal },
+            normal,
+            geometric_normal: normal,
             front_face,
             material,
+            u: 0.0,
+            v: 0.0,
+            dpdu: vec3!(0.0, 0.0, 0.0),
+            dpdv: vec3!(0.0, 0.0, 0.0),
+            dndu: vec3!(0.0, 0.0, 0.0),
+            dndv: vec3!(0.0, 0.0, 0.0),
+            dpdx: vec3!(0.0, 0.0, 0.0),
+            dpdy: vec3!(0.0, 0.0, 0.0),
+            duvdx: (0.0, 0.0),
+            duvdy: (0.0, 0.0),
         }
     }
+
+    pub fn with_uv(mut self, u: f64, v: f64) -> Self {
+        self.u = u;
+        self.v = v;
+        self
+    }
+
+    /// Whether `direction` is on the side of the actual surface the shading normal puts it on.
+    /// Directions that are not would let light leak through where the two normals differ.
+    pub fn is_consistent(&self, direction: &Vec3) -> bool {
+        direction.dot(&self.normal) * direction.dot(&self.geometric_normal) > 0.0
+    }
+
+    /// Partial derivatives of the point by the surface coordinates.
+    pub fn with_uv_derivatives(mut self, dpdu: Vec3, dpdv: Vec3) -> Self {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self
+    }
+
+    /// Unit tangent along `dpdu` perpendicular to `normal`, and the bitangent completing the frame
+    /// on the side of `dpdv`. Any frame around the normal if the surface has no coordinates.
+    pub fn tangents(&self, normal: &Vec3) -> (Vec3, Vec3) {
+        let tangent = self.dpdu - normal.dot(&self.dpdu) * normal;
+        if tangent.near_zero() {
+            let onb = Onb::new(normal);
+            return (onb.u, onb.v);
+        }
+        let tangent = tangent.unit_vec();
+        let bitangent = normal.cross(&tangent);
+        if bitangent.dot(&self.dpdv) < 0.0 {
+            (tangent, -bitangent)
+        } else {
+            (tangent, bitangent)
+        }
+    }
+
+    /// Partial derivatives of the normal by the surface coordinates, before it is turned to face the ray.
+    pub fn with_normal_derivatives(mut self, dndu: Vec3, dndv: Vec3) -> Self {
+        self.dndu = dndu;
+        self.dndv = dndv;
+        self
+    }
+
+    /// Normals at the points one pixel over and down, as far as the surface's curvature is known.
+    fn offset_normals(&self) -> (Vec3, Vec3) {
+        let side = if self.front_face { 1.0 } else { -1.0 };
+        let normal = |(du, dv): (f64, f64)| {
+            (self.normal + side * (du * self.dndu + dv * self.dndv)).unit_vec()
+        };
+        (normal(self.duvdx), normal(self.duvdy))
+    }
+
+    /// Finds the footprint of a pixel around the hit point from where the ray's offset rays
+    /// meet the plane tangent to the surface.
+    pub fn compute_differentials(&mut self, ray: &Ray) {
+        let Some(differential) = &ray.differential else {
+            return;
+        };
+        let n = self.geometric_normal;
+        let distance = n.dot(&self.point);
+        let on_plane = |origin: &Vec3, direction: &Vec3| {
+            let t = (distance - n.dot(origin)) / n.dot(direction);
+            t.is_finite().then(|| origin + t * direction)
+        };
+        let (Some(px), Some(py)) = (
+            on_plane(&differential.rx_origin, &differential.rx_direction),
+            on_plane(&differential.ry_origin, &differential.ry_direction),
+        ) else {
+            return;
+        };
+        self.dpdx = px - self.point;
+        self.dpdy = py - self.point;
+
+        // the two axes the plane is least steep along are enough to solve for the coordinates
+        let (a, b) = if n.x.abs() > n.y.abs() && n.x.abs() > n.z.abs() {
+            (1, 2)
+        } else if n.y.abs() > n.z.abs() {
+            (0, 2)
+        } else {
+            (0, 1)
+        };
+        let det = self.dpdu[a] * self.dpdv[b] - self.dpdv[a] * self.dpdu[b];
+        if det.abs() < 1.0e-12 {
+            return;
+        }
+        let solve = |d: &Vec3| {
+            (
+                (self.dpdv[b] * d[a] - self.dpdv[a] * d[b]) / det,
+                (self.dpdu[a] * d[b] - self.dpdu[b] * d[a]) / det,
+            )
+        };
+        self.duvdx = solve(&self.dpdx);
+        self.duvdy = solve(&self.dpdy);
+    }
+
+    /// Differentials of a ray mirrored at the hit point, each offset ray mirrored
+    /// where it meets the surface.
+    pub fn reflected_differential(&self, ray: &Ray) -> Option<Differential> {
+        let differential = ray.differential.as_ref()?;
+        let (nx, ny) = self.offset_normals();
+        Some(Differential {
+            rx_origin: self.point + self.dpdx,
+            rx_direction: differential.rx_direction.unit_vec().reflect(&nx),
+            ry_origin: self.point + self.dpdy,
+            ry_direction: differential.ry_direction.unit_vec().reflect(&ny),
+        })
+    }
+
+    /// Differentials of a ray refracted at the hit point with the ratio of indices of refraction,
+    /// none if either offset ray would be reflected instead.
+    pub fn refracted_differential(&self, ray: &Ray, refraction_ratio: f64) -> Option<Differential> {
+        let differential = ray.differential.as_ref()?;
+        let (nx, ny) = self.offset_normals();
+        let refract = |direction: &Vec3, normal: &Vec3| {
+            let d = direction.unit_vec();
+            let cos_theta = (-d).dot(normal).min(1.0);
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            (refraction_ratio * sin_theta <= 1.0).then(|| d.refract(normal, refraction_ratio))
+        };
+        Some(Differential {
+            rx_origin: self.point + self.dpdx,
+            rx_direction: refract(&differential.rx_direction, &nx)?,
+            ry_origin: self.point + self.dpdy,
+            ry_direction: refract(&differential.ry_direction, &ny)?,
+        })
+    }
+
+    /// Replaces the normal used for shading, e.g. one interpolated across a mesh.
+    /// shading_normal needs to be a unit vector on the same side as out_normal.
+    pub fn with_shading_normal(mut self, shading_normal: Vec3) -> Self {
+        self.normal = if self.front_face {
+            shading_normal
+        } else {
+            -shading_normal
+        };
+        self
+    }
 }
 
-pub trait Hittable: Sync {
-    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord>;
+pub trait Hittable: Send + Sync {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>>;
+
+    fn bounding_box(&self) -> Aabb;
+
+    /// Whether light is sampled directly from this object, which needs `pdf_value` and `random`.
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    /// Density of `random` picking `direction` from `origin` at `time`, in solid angle.
+    fn pdf_value(&self, _origin: &Vec3, _direction: &Vec3, _time: f64) -> f64 {
+        0.0
+    }
+
+    /// Direction from `origin` towards a random point on the object as it is at `time`.
+    fn random(&self, _origin: &Vec3, _time: f64, _rng: &mut SmallRng) -> Vec3 {
+        vec3!(1.0, 0.0, 0.0)
+    }
+
+    /// Lights among the parts of an object that is not a light as a whole, like shared geometry.
+    fn lights(&self) -> Vec<Arc<dyn Hittable>> {
+        Vec::new()
+    }
+}
+
+/// Lets an object be shared between the world's hierarchy and its lights.
+impl Hittable for Arc<dyn Hittable> {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.as_ref().hit(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.as_ref().bounding_box()
+    }
+
+    fn is_light(&self) -> bool {
+        self.as_ref().is_light()
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3, time: f64) -> f64 {
+        self.as_ref().pdf_value(origin, direction, time)
+    }
+
+    fn random(&self, origin: &Vec3, time: f64, rng: &mut SmallRng) -> Vec3 {
+        self.as_ref().random(origin, time, rng)
+    }
+
+    fn lights(&self) -> Vec<Arc<dyn Hittable>> {
+        self.as_ref().lights()
+    }
 }
 
 impl Hittable for Vec<Box<dyn Hittable>> {
-    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
         let mut closest_so_far = ray_t.max;
         let mut hit_record = None;
         for obj in self {
@@ -55,4 +309,10 @@ impl Hittable for Vec<Box<dyn Hittable>> {
         }
         hit_record
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.iter().fold(aabb::EMPTY, |bbox, obj| {
+            Aabb::enclosing(&bbox, &obj.bounding_box())
+        })
+    }
 }
@@ -0,0 +1,1329 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use super::bump::BumpMap;
+use super::camera::{Camera, Projection, Stereo, StereoLayout};
+use super::color::Color;
+use super::config::{Config, RenderSettings};
+use super::environment::{Environment, Sky};
+use super::input;
+use super::instance::Instance;
+use super::materials::*;
+use super::motion::Motion;
+use super::obj;
+use super::ray::Hittable;
+use super::sphere::Sphere;
+use super::texture::*;
+use super::transform::{Mat4, Transform};
+use super::triangle::{MeshError, Triangle, TriangleMesh};
+use super::vec3::*;
+use super::world::{Group, World};
+
+/// Scene description as written in a `.toml` or `.json` file.
+///
+/// ```toml
+/// [camera]
+/// look_from = [13.0, 2.0, 3.0]
+/// look_at = [0.0, 0.0, 0.0]
+/// vfov = 20.0
+///
+/// [render]
+/// samples = 100
+///
+/// [materials.ground]
+/// type = "lambertian"
+/// albedo = [0.5, 0.5, 0.5]
+///
+/// [[objects]]
+/// type = "sphere"
+/// center = [0.0, -1000.0, 0.0]
+/// radius = 1000.0
+/// material = "ground"
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDesc {
+    pub camera: CameraDesc,
+    /// The default white to blue sky if unset
+    pub environment: Option<EnvironmentDesc>,
+    /// Deprecated, the same as a constant `environment` of this color
+    pub background: Option<[f64; 3]>,
+    #[serde(default)]
+    pub render: RenderSettings,
+    #[serde(default)]
+    pub textures: BTreeMap<String, TextureDesc>,
+    #[serde(default)]
+    pub materials: BTreeMap<String, MaterialDesc>,
+    /// Objects built once and placed by any number of `instance` objects
+    #[serde(default)]
+    pub geometry: BTreeMap<String, ObjectDesc>,
+    #[serde(default)]
+    pub objects: Vec<ObjectDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDesc {
+    pub look_from: [f64; 3],
+    pub look_at: [f64; 3],
+    #[serde(default = "CameraDesc::default_vup")]
+    pub vup: [f64; 3],
+    #[serde(default = "CameraDesc::default_vfov")]
+    pub vfov: f64,
+    /// Lens diameter, zero keeps everything in focus
+    #[serde(default)]
+    pub aperture: f64,
+    /// Defaults to the distance to `look_at`
+    pub focus_distance: Option<f64>,
+    /// Point to focus on instead of giving `focus_distance`
+    pub focus_on: Option<[f64; 3]>,
+    #[serde(default)]
+    pub projection: ProjectionKind,
+    /// Renders a view for each eye, omni-directional stereo with the equirectangular projection
+    pub stereo: Option<StereoDesc>,
+    /// Time interval moving objects are blurred over
+    #[serde(default)]
+    pub shutter_open: f64,
+    #[serde(default)]
+    pub shutter_close: f64,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectionKind {
+    #[default]
+    Perspective,
+    Orthographic,
+    Fisheye,
+    Equirectangular,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StereoDesc {
+    pub eye_separation: f64,
+    /// Defaults to the focus distance
+    pub convergence: Option<f64>,
+    #[serde(default)]
+    pub layout: StereoLayoutKind,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StereoLayoutKind {
+    #[default]
+    SideBySide,
+    TopBottom,
+}
+
+impl CameraDesc {
+    fn default_vup() -> [f64; 3] {
+        [0.0, 1.0, 0.0]
+    }
+
+    fn default_vfov() -> f64 {
+        90.0
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvironmentDesc {
+    #[serde(rename = "type")]
+    pub kind: EnvironmentKind,
+    pub color: Option<[f64; 3]>,
+    pub bottom: Option<[f64; 3]>,
+    pub top: Option<[f64; 3]>,
+    /// Equirectangular image, relative to the scene file
+    pub path: Option<String>,
+    /// Degrees around the up axis
+    #[serde(default)]
+    pub rotation: f64,
+    #[serde(default = "EnvironmentDesc::default_intensity")]
+    pub intensity: f64,
+}
+
+impl EnvironmentDesc {
+    fn default_intensity() -> f64 {
+        1.0
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvironmentKind {
+    Constant,
+    Gradient,
+    Image,
+}
+
+/// Fields a material type does not need are ignored. They are all optional here rather than
+/// split into an internally tagged enum, which would lose the key and position in errors.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaterialDesc {
+    #[serde(rename = "type")]
+    pub kind: MaterialKind,
+    pub albedo: Option<[f64; 3]>,
+    /// Name of a texture to use instead of a constant `albedo`
+    pub texture: Option<String>,
+    /// Metal, glass or principled roughness from 0, perfectly smooth, to 1,
+    /// along the surface's u direction if `roughness_v` is set
+    pub roughness: Option<f64>,
+    /// Metal roughness along the surface's v direction, for brushed looking metals
+    pub roughness_v: Option<f64>,
+    /// Measured metal to use instead of `albedo`
+    pub conductor: Option<ConductorKind>,
+    /// Complex index of refraction `eta + i k` of a metal, instead of `albedo`
+    pub eta: Option<[f64; 3]>,
+    pub k: Option<[f64; 3]>,
+    pub ior: Option<f64>,
+    /// Measured glass to use instead of a constant `ior`, dispersing light when rendering spectrally
+    pub dielectric: Option<DielectricKind>,
+    /// `a` and `b` of Cauchy's equation for the index of refraction instead of `ior`, λ in micrometers
+    pub cauchy: Option<[f64; 2]>,
+    /// Coefficients of the Sellmeier equation for the index of refraction instead of `ior`,
+    /// `sellmeier_c` in square micrometers
+    pub sellmeier_b: Option<[f64; 3]>,
+    pub sellmeier_c: Option<[f64; 3]>,
+    /// Color glass leaves light after it travels a distance of 1 / `density` inside, clear if unset
+    pub tint: Option<[f64; 3]>,
+    pub density: Option<f64>,
+    pub emit: Option<[f64; 3]>,
+    /// Principled parameters in [0, 1], over a base color from `albedo` or `texture`.
+    /// Glass-like transmission refracts with `ior`, 1.5 if unset
+    pub metallic: Option<f64>,
+    pub specular: Option<f64>,
+    pub sheen: Option<f64>,
+    pub clearcoat: Option<f64>,
+    pub clearcoat_roughness: Option<f64>,
+    pub transmission: Option<f64>,
+    pub subsurface: Option<f64>,
+    /// Name of a texture of tangent space normals, for any type of material
+    pub normal_map: Option<String>,
+    /// Name of a texture of heights, for any type of material
+    pub bump_map: Option<String>,
+    /// Height of a white texel in the bump map
+    pub bump_scale: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MaterialKind {
+    Lambertian,
+    Metal,
+    Glass,
+    #[serde(rename = "diffuse_light")]
+    DiffuseLight,
+    Principled,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConductorKind {
+    Gold,
+    Silver,
+    Copper,
+    #[serde(alias = "aluminum")]
+    Aluminium,
+    Chromium,
+    Iron,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DielectricKind {
+    Bk7,
+    FusedSilica,
+    Sf11,
+    Diamond,
+}
+
+/// Fields a texture type does not need are ignored, like for materials.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TextureDesc {
+    #[serde(rename = "type")]
+    pub kind: TextureKind,
+    pub color: Option<[f64; 3]>,
+    /// Checker cube edge length
+    pub size: Option<f64>,
+    pub even: Option<[f64; 3]>,
+    pub odd: Option<[f64; 3]>,
+    pub noise: Option<NoiseKindDesc>,
+    /// Noise frequency
+    #[serde(default = "TextureDesc::default_scale")]
+    pub scale: f64,
+    /// Picks the noise pattern
+    #[serde(default)]
+    pub seed: u64,
+    /// Noise colors, black to white if unset
+    pub low: Option<[f64; 3]>,
+    pub high: Option<[f64; 3]>,
+    /// Image file, relative to the scene file
+    pub path: Option<String>,
+    /// The image holds values such as normals or heights rather than gamma encoded colors
+    #[serde(default)]
+    pub data: bool,
+}
+
+impl TextureDesc {
+    fn default_scale() -> f64 {
+        1.0
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextureKind {
+    Solid,
+    Checker,
+    Noise,
+    Image,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoiseKindDesc {
+    Perlin,
+    Turbulence,
+    Marble,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ObjectDesc {
+    #[serde(rename = "type")]
+    pub kind: ObjectKind,
+    /// Optional for OBJ files and instances, replacing their own materials
+    pub material: Option<String>,
+    /// OBJ file, relative to the scene file
+    pub path: Option<String>,
+    /// Name of the shared geometry an instance places
+    pub geometry: Option<String>,
+    /// Scales along each axis, then rotates by degrees around the x, y and z axes, then translates
+    pub scale: Option<[f64; 3]>,
+    pub rotate: Option<[f64; 3]>,
+    pub translate: Option<[f64; 3]>,
+    /// For objects other than spheres, moves them after `translate`
+    pub center: Option<[f64; 3]>,
+    /// Moves `center` from where it is at time 0 by this much per unit of time
+    pub velocity: Option<[f64; 3]>,
+    /// Positions of `center` over time instead of a fixed one
+    pub keyframes: Option<Vec<KeyframeDesc>>,
+    pub radius: Option<f64>,
+    pub vertices: Option<[[f64; 3]; 3]>,
+    pub positions: Option<Vec<[f64; 3]>>,
+    pub normals: Option<Vec<[f64; 3]>>,
+    pub uvs: Option<Vec<[f64; 2]>>,
+    pub indices: Option<Vec<[usize; 3]>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyframeDesc {
+    pub time: f64,
+    pub center: [f64; 3],
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectKind {
+    Sphere,
+    Triangle,
+    Mesh,
+    Obj,
+    Instance,
+}
+
+/// Reads a scene file and builds a render configuration from it.
+/// Fields set in `overrides` take precedence over the scene's render settings.
+pub fn load(path: &Path, overrides: RenderSettings) -> Result<Config, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let extension = path.extension().and_then(|e| e.to_str());
+    let json = match extension.map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("toml") => false,
+        Some("json") => true,
+        _ => {
+            return Err(format!(
+                "{}: unsupported scene format, expected .toml or .json",
+                path.display()
+            ))
+        }
+    };
+    if json {
+        parse_json(&text)
+    } else {
+        parse_toml(&text)
+    }
+    .and_then(|scene| {
+        scene
+            .build(path.parent().unwrap_or(Path::new("")), overrides)
+            .map_err(|e| with_position(&text, json, &e))
+    })
+    .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn parse_toml(text: &str) -> Result<SceneDesc, String> {
+    serde_path_to_error::deserialize(toml::Deserializer::new(text)).map_err(|e| {
+        let key = key_prefix(e.path());
+        let inner = e.into_inner();
+        let message = inner.message().trim().replace('\n', ": ");
+        match inner.span() {
+            Some(span) => {
+                let (line, column) = line_column(text, span.start);
+                format!("{}{} at line {} column {}", key, message, line, column)
+            }
+            None => format!("{}{}", key, message),
+        }
+    })
+}
+
+pub fn parse_json(text: &str) -> Result<SceneDesc, String> {
+    let mut deserializer = serde_json::Deserializer::from_str(text);
+    serde_path_to_error::deserialize(&mut deserializer)
+        .map_err(|e| format!("{}{}", key_prefix(e.path()), e.inner()))
+}
+
+fn key_prefix(path: &serde_path_to_error::Path) -> String {
+    let key = path.to_string();
+    if key == "." {
+        String::new()
+    } else {
+        format!("{}: ", key)
+    }
+}
+
+/// 1-based line and column of a byte offset.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+    (line, column)
+}
+
+/// One step of the path to the value a build error is about.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Error building a scene description, about the value at `path` such as `objects[2].material`.
+/// Kept apart from the message until it is reported, to find the value in the scene's text.
+#[derive(Debug)]
+pub struct BuildError {
+    pub path: Vec<PathSegment>,
+    pub message: String,
+}
+
+impl BuildError {
+    fn new(key: &str, message: impl Into<String>) -> Self {
+        Self {
+            path: vec![PathSegment::Key(key.to_string())],
+            message: message.into(),
+        }
+    }
+
+    /// The same error, about a value inside `key`.
+    fn in_key(mut self, key: &str) -> Self {
+        self.path.insert(0, PathSegment::Key(key.to_string()));
+        self
+    }
+
+    fn in_index(mut self, index: usize) -> Self {
+        self.path.insert(0, PathSegment::Index(index));
+        self
+    }
+}
+
+impl From<MeshError> for BuildError {
+    fn from(error: MeshError) -> Self {
+        Self::new(error.argument, error.message)
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.path.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) => {
+                    if i > 0 {
+                        write!(f, ".")?;
+                    }
+                    // quoted like TOML does when the key is not just letters, digits, - and _
+                    let bare = !key.is_empty()
+                        && key
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+                    if bare {
+                        write!(f, "{}", key)?;
+                    } else {
+                        write!(f, "{:?}", key)?;
+                    }
+                }
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Reports the error with the line and column of the value it is about, found in the scene's
+/// text, or of the closest enclosing one present when the error is about a missing key.
+fn with_position(text: &str, json: bool, error: &BuildError) -> String {
+    let offset = if json {
+        json_offset(text, &error.path)
+    } else {
+        toml_offset(text, &error.path)
+    };
+    match offset {
+        Some(offset) => {
+            let (line, column) = line_column(text, offset);
+            format!("{} at line {} column {}", error, line, column)
+        }
+        None => error.to_string(),
+    }
+}
+
+fn toml_offset(text: &str, path: &[PathSegment]) -> Option<usize> {
+    let document = toml_edit::ImDocument::parse(text).ok()?;
+    let mut item = document.as_item();
+    let mut offset = None;
+    for segment in path {
+        let span = match segment {
+            PathSegment::Key(key) => {
+                let Some((key, value)) = item.as_table_like().and_then(|t| t.get_key_value(key))
+                else {
+                    break;
+                };
+                item = value;
+                // keys of tables only have a position through their header
+                key.span()
+                    .or_else(|| value.as_table().and_then(|t| t.span()))
+            }
+            PathSegment::Index(i) => {
+                let Some(value) = item.get(*i) else {
+                    break;
+                };
+                item = value;
+                match item {
+                    toml_edit::Item::Table(table) => table.span(),
+                    _ => item.span(),
+                }
+            }
+        };
+        offset = span.map(|span| span.start).or(offset);
+    }
+    offset
+}
+
+/// Walks the JSON text itself, which serde_json keeps no positions of once it is parsed.
+fn json_offset(text: &str, path: &[PathSegment]) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let skip_whitespace = |mut i: usize| {
+        while bytes.get(i).is_some_and(|b| b.is_ascii_whitespace()) {
+            i += 1;
+        }
+        i
+    };
+    // end of the string starting at `i`
+    let skip_string = |mut i: usize| {
+        i += 1;
+        while i < bytes.len() && bytes[i] != b'"' {
+            i += if bytes[i] == b'\\' { 2 } else { 1 };
+        }
+        i + 1
+    };
+    // end of the value starting at `i`
+    let skip_value = |mut i: usize| {
+        let mut depth = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'"' => {
+                    i = skip_string(i);
+                    if depth == 0 {
+                        return i;
+                    }
+                    continue;
+                }
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' if depth == 0 => return i,
+                b'}' | b']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return i + 1;
+                    }
+                }
+                b',' if depth == 0 => return i,
+                _ => {}
+            }
+            i += 1;
+        }
+        i
+    };
+
+    let mut i = skip_whitespace(0);
+    let mut offset = None;
+    for segment in path {
+        match segment {
+            PathSegment::Key(key) => {
+                if bytes.get(i) != Some(&b'{') {
+                    return offset;
+                }
+                i = skip_whitespace(i + 1);
+                loop {
+                    if bytes.get(i) != Some(&b'"') {
+                        return offset;
+                    }
+                    let end = skip_string(i);
+                    // keys are compared unescaped
+                    let found = text
+                        .get(i..end)
+                        .and_then(|raw| serde_json::from_str::<String>(raw).ok())
+                        .is_some_and(|k| k == *key);
+                    let key_start = i;
+                    i = skip_whitespace(end);
+                    if bytes.get(i) != Some(&b':') {
+                        return offset;
+                    }
+                    i = skip_whitespace(i + 1);
+                    if found {
+                        offset = Some(key_start);
+                        break;
+                    }
+                    i = skip_whitespace(skip_value(i));
+                    if bytes.get(i) != Some(&b',') {
+                        return offset;
+                    }
+                    i = skip_whitespace(i + 1);
+                }
+            }
+            PathSegment::Index(index) => {
+                if bytes.get(i) != Some(&b'[') {
+                    return offset;
+                }
+                i = skip_whitespace(i + 1);
+                for _ in 0..*index {
+                    i = skip_whitespace(skip_value(i));
+                    if bytes.get(i) != Some(&b',') {
+                        return offset;
+                    }
+                    i = skip_whitespace(i + 1);
+                }
+                if bytes.get(i).is_none_or(|&b| b == b']') {
+                    return offset;
+                }
+                offset = Some(i);
+            }
+        }
+    }
+    offset
+}
+
+fn vec3_of(v: [f64; 3]) -> Vec3 {
+    vec3!(v[0], v[1], v[2])
+}
+
+fn color_of(c: [f64; 3]) -> Color {
+    Color::new(c[0], c[1], c[2])
+}
+
+impl SceneDesc {
+    /// Relative paths in the scene are resolved against `base`.
+    pub fn build(self, base: &Path, overrides: RenderSettings) -> Result<Config, BuildError> {
+        for (key, value) in [
+            ("width", self.render.width),
+            ("height", self.render.height),
+            ("samples", self.render.samples),
+            ("max_depth", self.render.max_depth),
+        ] {
+            if value == Some(0) {
+                return Err(BuildError::new(key, "must be at least 1").in_key("render"));
+            }
+        }
+        let settings = overrides.or(self.render);
+
+        let mut textures = HashMap::new();
+        for (name, desc) in self.textures {
+            let texture = desc
+                .build(base)
+                .map_err(|e| e.in_key(&name).in_key("textures"))?;
+            textures.insert(name, texture);
+        }
+
+        let mut materials = HashMap::new();
+        for (name, desc) in self.materials {
+            let material = desc
+                .build(&textures)
+                .map_err(|e| e.in_key(&name).in_key("materials"))?;
+            materials.insert(name, Arc::new(material));
+        }
+
+        let shutter = (self.camera.shutter_open, self.camera.shutter_close);
+        let camera = self
+            .camera
+            .build(&settings)
+            .map_err(|e| e.in_key("camera"))?;
+
+        let no_geometry = HashMap::new();
+        let mut geometry = HashMap::new();
+        for (name, desc) in self.geometry {
+            if let ObjectKind::Instance = desc.kind {
+                return Err(
+                    BuildError::new("type", "instances can not be shared geometry")
+                        .in_key(&name)
+                        .in_key("geometry"),
+                );
+            }
+            let objects = desc
+                .build(base, &materials, &no_geometry, shutter)
+                .map_err(|e| e.in_key(&name).in_key("geometry"))?;
+            let group: Arc<dyn Hittable> = Arc::new(Group::new(objects));
+            geometry.insert(name, group);
+        }
+
+        let mut world = Vec::<Box<dyn Hittable>>::new();
+        for (i, object) in self.objects.into_iter().enumerate() {
+            world.extend(
+                object
+                    .build(base, &materials, &geometry, shutter)
+                    .map_err(|e| e.in_index(i).in_key("objects"))?,
+            );
+        }
+
+        let environment = match (self.environment, self.background) {
+            (Some(_), Some(_)) => {
+                return Err(BuildError::new(
+                    "background",
+                    "can not be combined with environment",
+                ))
+            }
+            (Some(desc), None) => desc.build(base).map_err(|e| e.in_key("environment"))?,
+            (None, Some(color)) => Environment::new(Sky::Constant(color_of(color))),
+            (None, None) => Environment::default(),
+        };
+
+        Ok(Config::new(
+            "Raytracer",
+            camera,
+            World::new(world, environment),
+        ))
+    }
+}
+
+fn required<T>(value: Option<T>, key: &str) -> Result<T, BuildError> {
+    value.ok_or_else(|| BuildError::new(key, "missing field"))
+}
+
+impl CameraDesc {
+    fn build(self, settings: &RenderSettings) -> Result<Camera, BuildError> {
+        if self.aperture < 0.0 {
+            return Err(BuildError::new("aperture", "must not be negative"));
+        }
+        if self.shutter_close < self.shutter_open {
+            return Err(BuildError::new(
+                "shutter_close",
+                "must not be before shutter_open",
+            ));
+        }
+        let (look_from, look_at, vup) = (
+            vec3_of(self.look_from),
+            vec3_of(self.look_at),
+            vec3_of(self.vup),
+        );
+        if (look_at - look_from).near_zero() {
+            return Err(BuildError::new("look_at", "must not be look_from"));
+        }
+        if vup.near_zero()
+            || vup
+                .unit_vec()
+                .cross(&(look_at - look_from).unit_vec())
+                .near_zero()
+        {
+            return Err(BuildError::new(
+                "vup",
+                "must not be along the view direction",
+            ));
+        }
+        let camera = Camera::new(
+            settings.width(),
+            settings.height(),
+            look_from,
+            look_at,
+            vup,
+            self.vfov,
+            settings.samples(),
+            settings.max_depth(),
+        )
+        .with_aperture(self.aperture)
+        .with_shutter(self.shutter_open, self.shutter_close)
+        .with_spectral(settings.spectral())
+        .with_projection(match self.projection {
+            ProjectionKind::Perspective => Projection::Perspective,
+            ProjectionKind::Orthographic => Projection::Orthographic,
+            ProjectionKind::Fisheye => Projection::Fisheye,
+            ProjectionKind::Equirectangular => Projection::Equirectangular,
+        });
+
+        let camera = match (self.focus_distance, self.focus_on) {
+            (Some(_), Some(_)) => {
+                return Err(BuildError::new(
+                    "focus_on",
+                    "can not be combined with focus_distance",
+                ))
+            }
+            (Some(distance), None) if distance <= 0.0 => {
+                return Err(BuildError::new("focus_distance", "must be positive"))
+            }
+            (Some(distance), None) => camera.with_focus_distance(distance),
+            (None, Some(point)) => {
+                let camera = camera.with_focus_on(vec3_of(point));
+                if camera.focus_distance() <= 0.0 {
+                    return Err(BuildError::new(
+                        "focus_on",
+                        "must be in front of the camera",
+                    ));
+                }
+                camera
+            }
+            (None, None) => camera,
+        };
+
+        match self.stereo {
+            Some(desc) => {
+                let convergence = desc.convergence.unwrap_or(camera.focus_distance());
+                let stereo = desc.build(convergence).map_err(|e| e.in_key("stereo"))?;
+                Ok(camera.with_stereo(stereo))
+            }
+            None => Ok(camera),
+        }
+    }
+}
+
+impl StereoDesc {
+    fn build(self, convergence: f64) -> Result<Stereo, BuildError> {
+        if self.eye_separation < 0.0 {
+            return Err(BuildError::new("eye_separation", "must not be negative"));
+        }
+        if convergence <= 0.0 {
+            return Err(BuildError::new("convergence", "must be positive"));
+        }
+        Ok(Stereo {
+            eye_separation: self.eye_separation,
+            convergence,
+            layout: match self.layout {
+                StereoLayoutKind::SideBySide => StereoLayout::SideBySide,
+                StereoLayoutKind::TopBottom => StereoLayout::TopBottom,
+            },
+        })
+    }
+}
+
+impl EnvironmentDesc {
+    fn build(self, base: &Path) -> Result<Environment, BuildError> {
+        let sky = match self.kind {
+            EnvironmentKind::Constant => Sky::Constant(color_of(required(self.color, "color")?)),
+            EnvironmentKind::Gradient => Sky::Gradient {
+                bottom: color_of(required(self.bottom, "bottom")?),
+                top: color_of(required(self.top, "top")?),
+            },
+            EnvironmentKind::Image => {
+                let path = base.join(required(self.path, "path")?);
+                Sky::Map(input::load(&path).map_err(|e| BuildError::new("path", e.to_string()))?)
+            }
+        };
+        Ok(Environment::new(sky)
+            .with_rotation(self.rotation)
+            .with_intensity(self.intensity))
+    }
+}
+
+impl TextureDesc {
+    fn build(self, base: &Path) -> Result<Arc<dyn Texture>, BuildError> {
+        Ok(match self.kind {
+            TextureKind::Solid => {
+                Arc::new(SolidColor::new(color_of(required(self.color, "color")?)))
+            }
+            TextureKind::Checker => {
+                let size = required(self.size, "size")?;
+                if size <= 0.0 {
+                    return Err(BuildError::new("size", "must be positive"));
+                }
+                Arc::new(Checker::from_colors(
+                    size,
+                    color_of(required(self.even, "even")?),
+                    color_of(required(self.odd, "odd")?),
+                ))
+            }
+            TextureKind::Noise => {
+                let kind = match required(self.noise, "noise")? {
+                    NoiseKindDesc::Perlin => NoiseKind::Perlin,
+                    NoiseKindDesc::Turbulence => NoiseKind::Turbulence,
+                    NoiseKindDesc::Marble => NoiseKind::Marble,
+                };
+                Arc::new(Noise::new(kind, self.scale, self.seed).with_colors(
+                    color_of(self.low.unwrap_or([0.0; 3])),
+                    color_of(self.high.unwrap_or([1.0; 3])),
+                ))
+            }
+            TextureKind::Image => {
+                let path = base.join(required(self.path, "path")?);
+                let image = if self.data {
+                    input::load_data(&path)
+                } else {
+                    input::load(&path)
+                };
+                Arc::new(ImageTexture::new(
+                    image.map_err(|e| BuildError::new("path", e.to_string()))?,
+                ))
+            }
+        })
+    }
+}
+
+impl MaterialDesc {
+    fn build(self, textures: &HashMap<String, Arc<dyn Texture>>) -> Result<Material, BuildError> {
+        let albedo = || -> Result<Arc<dyn Texture>, BuildError> {
+            match (self.albedo, &self.texture) {
+                (Some(_), Some(_)) => Err(BuildError::new(
+                    "texture",
+                    "can not be combined with albedo",
+                )),
+                (Some(albedo), None) => Ok(Arc::new(SolidColor::new(color_of(albedo)))),
+                (None, Some(name)) => texture_ref(textures, "texture", name),
+                (None, None) => Err(BuildError::new("albedo", "missing field")),
+            }
+        };
+        let bump_map = match (&self.normal_map, &self.bump_map) {
+            (Some(_), Some(_)) => {
+                return Err(BuildError::new(
+                    "bump_map",
+                    "can not be combined with normal_map",
+                ))
+            }
+            (Some(name), None) => Some(BumpMap::Normal(texture_ref(textures, "normal_map", name)?)),
+            (None, Some(name)) => Some(BumpMap::Height {
+                texture: texture_ref(textures, "bump_map", name)?,
+                scale: self.bump_scale.unwrap_or(1.0),
+            }),
+            (None, None) => None,
+        };
+        let material = match self.kind {
+            MaterialKind::Lambertian => Material::Lambertian(Lambertian::textured(albedo()?)),
+            MaterialKind::Metal => {
+                let roughness = self.roughness.unwrap_or(0.0);
+                let colored = self.albedo.is_some() || self.texture.is_some();
+                let metal = match (self.conductor, self.eta, self.k) {
+                    (Some(_), Some(_), _) | (Some(_), _, Some(_)) => {
+                        return Err(BuildError::new(
+                            "conductor",
+                            "can not be combined with eta or k",
+                        ))
+                    }
+                    (Some(_), None, None) | (None, Some(_), _) | (None, _, Some(_)) if colored => {
+                        return Err(BuildError::new(
+                            "albedo",
+                            "can not be combined with conductor, eta or k",
+                        ))
+                    }
+                    (Some(conductor), None, None) => {
+                        let (eta, k) = match conductor {
+                            ConductorKind::Gold => Conductor::Gold,
+                            ConductorKind::Silver => Conductor::Silver,
+                            ConductorKind::Copper => Conductor::Copper,
+                            ConductorKind::Aluminium => Conductor::Aluminium,
+                            ConductorKind::Chromium => Conductor::Chromium,
+                            ConductorKind::Iron => Conductor::Iron,
+                        }
+                        .ior();
+                        Metal::conductor(eta, k, roughness)
+                    }
+                    (None, Some(eta), k) => {
+                        Metal::conductor(color_of(eta), color_of(required(k, "k")?), roughness)
+                    }
+                    (None, None, Some(_)) => return Err(BuildError::new("eta", "missing field")),
+                    (None, None, None) => Metal::textured(albedo()?, roughness),
+                };
+                Material::Metal(match self.roughness_v {
+                    Some(roughness_v) => metal.with_anisotropic_roughness(roughness, roughness_v),
+                    None => metal,
+                })
+            }
+            MaterialKind::Glass => {
+                let index = match (self.ior, self.dielectric, self.cauchy, self.sellmeier_b) {
+                    (Some(ior), None, None, None) => RefractiveIndex::Constant(ior),
+                    (None, Some(dielectric), None, None) => match dielectric {
+                        DielectricKind::Bk7 => Dielectric::Bk7,
+                        DielectricKind::FusedSilica => Dielectric::FusedSilica,
+                        DielectricKind::Sf11 => Dielectric::Sf11,
+                        DielectricKind::Diamond => Dielectric::Diamond,
+                    }
+                    .index(),
+                    (None, None, Some([a, b]), None) => RefractiveIndex::Cauchy { a, b },
+                    (None, None, None, Some(b)) => RefractiveIndex::Sellmeier {
+                        b,
+                        c: required(self.sellmeier_c, "sellmeier_c")?,
+                    },
+                    (None, None, None, None) => {
+                        return Err(BuildError::new("ior", "missing field"))
+                    }
+                    _ => {
+                        return Err(BuildError::new(
+                            "ior",
+                            "only one of ior, dielectric, cauchy and sellmeier_b can be given",
+                        ))
+                    }
+                };
+                let glass = Glass::dispersive(index).with_roughness(self.roughness.unwrap_or(0.0));
+                Material::Glass(match self.tint {
+                    Some(tint) => {
+                        glass.with_absorption(color_of(tint), self.density.unwrap_or(1.0))
+                    }
+                    None => glass,
+                })
+            }
+            MaterialKind::DiffuseLight => {
+                Material::DiffuseLight(DiffuseLight::new(color_of(required(self.emit, "emit")?)))
+            }
+            MaterialKind::Principled => Material::Principled(
+                Principled::textured(albedo()?)
+                    .with_metallic(self.metallic.unwrap_or(0.0))
+                    .with_roughness(self.roughness.unwrap_or(0.5))
+                    .with_specular(self.specular.unwrap_or(0.5))
+                    .with_sheen(self.sheen.unwrap_or(0.0))
+                    .with_clearcoat(
+                        self.clearcoat.unwrap_or(0.0),
+                        self.clearcoat_roughness.unwrap_or(0.1),
+                    )
+                    .with_transmission(self.transmission.unwrap_or(0.0), self.ior.unwrap_or(1.5))
+                    .with_subsurface(self.subsurface.unwrap_or(0.0)),
+            ),
+        };
+        Ok(match bump_map {
+            Some(bump_map) => Material::Bumped(Bumped::new(material, bump_map)),
+            None => material,
+        })
+    }
+}
+
+fn texture_ref(
+    textures: &HashMap<String, Arc<dyn Texture>>,
+    key: &str,
+    name: &str,
+) -> Result<Arc<dyn Texture>, BuildError> {
+    textures
+        .get(name)
+        .cloned()
+        .ok_or_else(|| BuildError::new(key, format!("unknown texture `{}`", name)))
+}
+
+fn material_ref(
+    materials: &HashMap<String, Arc<Material>>,
+    name: &str,
+) -> Result<Arc<Material>, BuildError> {
+    materials
+        .get(name)
+        .cloned()
+        .ok_or_else(|| BuildError::new("material", format!("unknown material `{}`", name)))
+}
+
+fn identity() -> Transform {
+    Transform::new(Mat4::IDENTITY).unwrap()
+}
+
+fn geometry_ref(
+    geometry: &HashMap<String, Arc<dyn Hittable>>,
+    name: &str,
+) -> Result<Arc<dyn Hittable>, BuildError> {
+    geometry
+        .get(name)
+        .cloned()
+        .ok_or_else(|| BuildError::new("geometry", format!("unknown geometry `{}`", name)))
+}
+
+/// Fixed or linear motion of `center`, or keyframed motion instead, none if neither is given.
+/// Linear motion only needs to be known while the camera's `shutter` is open.
+fn motion(
+    center: Option<[f64; 3]>,
+    velocity: Option<[f64; 3]>,
+    keyframes: Option<Vec<KeyframeDesc>>,
+    shutter: (f64, f64),
+) -> Result<Option<Motion>, BuildError> {
+    Ok(match (center, velocity, keyframes) {
+        (None, None, Some(keyframes)) => Some(
+            Motion::keyframed(
+                keyframes
+                    .into_iter()
+                    .map(|k| (k.time, vec3_of(k.center)))
+                    .collect(),
+            )
+            .map_err(|e| BuildError::new("keyframes", e))?,
+        ),
+        (_, _, Some(_)) => {
+            return Err(BuildError::new(
+                "keyframes",
+                "can not be combined with center or velocity",
+            ))
+        }
+        (None, _, None) => None,
+        (Some(center), None, None) => Some(Motion::fixed(vec3_of(center))),
+        (Some(center), Some(velocity), None) => {
+            let (center, velocity) = (vec3_of(center), vec3_of(velocity));
+            let (open, close) = shutter;
+            Some(Motion::linear(
+                open,
+                center + open * velocity,
+                close,
+                center + close * velocity,
+            ))
+        }
+    })
+}
+
+impl ObjectDesc {
+    /// OBJ files give one object per mesh, so emissive ones can be sampled as lights.
+    /// Transformed or moving objects other than spheres are wrapped in instances.
+    fn build(
+        self,
+        base: &Path,
+        materials: &HashMap<String, Arc<Material>>,
+        geometry: &HashMap<String, Arc<dyn Hittable>>,
+        shutter: (f64, f64),
+    ) -> Result<Vec<Box<dyn Hittable>>, BuildError> {
+        let material = match self.material.as_deref() {
+            Some(name) => Some(material_ref(materials, name)?),
+            None => None,
+        };
+        let transform = self.transform()?;
+        // spheres move their center, other objects are moved as a whole from their origin
+        let (center, placement) = match self.kind {
+            ObjectKind::Sphere => (
+                motion(self.center, self.velocity, self.keyframes, shutter)?,
+                None,
+            ),
+            _ => (
+                None,
+                motion(
+                    self.center.or(self.velocity.map(|_| [0.0; 3])),
+                    self.velocity,
+                    self.keyframes,
+                    shutter,
+                )?,
+            ),
+        };
+
+        let objects: Vec<Box<dyn Hittable>> = match self.kind {
+            ObjectKind::Sphere => {
+                vec![Box::new(Sphere::moving(
+                    required(center, "center")?,
+                    required(self.radius, "radius")?,
+                    required(material, "material")?,
+                ))]
+            }
+            ObjectKind::Triangle => {
+                let [p0, p1, p2] = required(self.vertices, "vertices")?;
+                vec![Box::new(Triangle::new(
+                    vec3_of(p0),
+                    vec3_of(p1),
+                    vec3_of(p2),
+                    required(material, "material")?,
+                ))]
+            }
+            ObjectKind::Mesh => {
+                let mesh = TriangleMesh::new(
+                    required(self.positions, "positions")?
+                        .into_iter()
+                        .map(vec3_of)
+                        .collect(),
+                    self.normals
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(vec3_of)
+                        .collect(),
+                    self.uvs
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|uv| (uv[0], uv[1]))
+                        .collect(),
+                    required(self.indices, "indices")?,
+                    required(material, "material")?,
+                )?;
+                vec![Box::new(mesh)]
+            }
+            ObjectKind::Obj => {
+                let path = base.join(required(self.path, "path")?);
+                obj::load(&path, material)
+                    .map_err(|e| BuildError::new("path", e.to_string()))?
+                    .into_iter()
+                    .map(|mesh| Box::new(mesh) as Box<dyn Hittable>)
+                    .collect()
+            }
+            ObjectKind::Instance => {
+                let object = geometry_ref(geometry, &required(self.geometry, "geometry")?)?;
+                let mut instance = Instance::new(object, transform.unwrap_or_else(identity));
+                if let Some(motion) = placement {
+                    instance = instance.with_motion(motion);
+                }
+                if let Some(material) = material {
+                    instance = instance.with_material(material);
+                }
+                return Ok(vec![Box::new(instance)]);
+            }
+        };
+
+        if transform.is_none() && placement.is_none() {
+            return Ok(objects);
+        }
+        let transform = transform.unwrap_or_else(identity);
+        Ok(objects
+            .into_iter()
+            .map(|object| {
+                let instance = Instance::new(Arc::from(object), transform);
+                let instance: Box<dyn Hittable> = match &placement {
+                    Some(motion) => Box::new(instance.with_motion(motion.clone())),
+                    None => Box::new(instance),
+                };
+                instance
+            })
+            .collect())
+    }
+
+    /// None if the object is not scaled, rotated or translated.
+    fn transform(&self) -> Result<Option<Transform>, BuildError> {
+        if self.scale.is_none() && self.rotate.is_none() && self.translate.is_none() {
+            return Ok(None);
+        }
+        let mut matrix = Mat4::scaling(vec3_of(self.scale.unwrap_or([1.0; 3])));
+        if let Some([x, y, z]) = self.rotate {
+            matrix = Mat4::rotation(vec3!(1.0, 0.0, 0.0), x) * matrix;
+            matrix = Mat4::rotation(vec3!(0.0, 1.0, 0.0), y) * matrix;
+            matrix = Mat4::rotation(vec3!(0.0, 0.0, 1.0), z) * matrix;
+        }
+        matrix = Mat4::translation(vec3_of(self.translate.unwrap_or_default())) * matrix;
+        Transform::new(matrix)
+            .map(Some)
+            .map_err(|e| BuildError::new("scale", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"[materials.red]
+type = "lambertian"
+
+[[objects]]
+type = "sphere"
+
+[[objects]]
+type = "sphere"
+material = "blue"
+"#;
+
+    const JSON: &str = r#"{
+  "materials": {"red": {"type": "lambertian"}},
+  "objects": [
+    {"type": "sphere", "material": "r\"ed"},
+    {"type": "sphere", "material": "blue"}
+  ]
+}"#;
+
+    /// The error building a scene, as `load` reports it.
+    fn build_error(text: &str, json: bool) -> String {
+        let scene = if json {
+            parse_json(text)
+        } else {
+            parse_toml(text)
+        }
+        .unwrap();
+        let Err(error) = scene.build(Path::new(""), RenderSettings::default()) else {
+            panic!("{} was accepted", text);
+        };
+        with_position(text, json, &error)
+    }
+
+    #[test]
+    fn build_errors_point_at_their_key() {
+        let error = BuildError::new("material", "unknown material `blue`")
+            .in_index(1)
+            .in_key("objects");
+        assert!(with_position(TOML, false, &error).ends_with("at line 9 column 1"));
+        assert!(with_position(JSON, true, &error).ends_with("at line 5 column 24"));
+    }
+
+    #[test]
+    fn missing_keys_point_at_their_table() {
+        let error = BuildError::new("albedo", "missing field")
+            .in_key("red")
+            .in_key("materials");
+        assert!(with_position(TOML, false, &error).ends_with("at line 1 column 12"));
+        assert!(with_position(JSON, true, &error).ends_with("at line 2 column 17"));
+    }
+
+    #[test]
+    fn quoted_keys_stay_one_key() {
+        let toml = r#"[materials."a.b"]
+type = "lambertian"
+
+[camera]
+look_from = [0.0, 0.0, 1.0]
+look_at = [0.0, 0.0, 0.0]
+"#;
+        let json = r#"{
+  "materials": {"a.b": {"type": "lambertian"}},
+  "camera": {"look_from": [0.0, 0.0, 1.0], "look_at": [0.0, 0.0, 0.0]}
+}"#;
+        assert_eq!(
+            build_error(toml, false),
+            "materials.\"a.b\".albedo: missing field at line 1 column 12"
+        );
+        assert_eq!(
+            build_error(json, true),
+            "materials.\"a.b\".albedo: missing field at line 2 column 17"
+        );
+    }
+
+    #[test]
+    fn file_errors_are_about_their_path() {
+        let toml = r#"[camera]
+look_from = [0.0, 0.0, 1.0]
+look_at = [0.0, 0.0, 0.0]
+
+[[objects]]
+type = "obj"
+path = "missing.obj"
+"#;
+        assert_eq!(
+            build_error(toml, false),
+            "objects[0].path: open file failed at line 7 column 1"
+        );
+    }
+
+    #[test]
+    fn degenerate_cameras_are_rejected() {
+        for (camera, expected) in [
+            (
+                "look_from = [1.0, 2.0, 3.0]\nlook_at = [1.0, 2.0, 3.0]",
+                "camera.look_at: must not be look_from",
+            ),
+            (
+                "look_from = [0.0, 5.0, 0.0]\nlook_at = [0.0, 0.0, 0.0]",
+                "camera.vup: must not be along the view direction",
+            ),
+        ] {
+            let scene = parse_toml(&format!("[camera]\n{}\n", camera)).unwrap();
+            let Err(error) = scene.build(Path::new(""), RenderSettings::default()) else {
+                panic!("{} was accepted", camera);
+            };
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use super::color::Color;
+
+/// Shortest and longest wavelengths sampled, in nanometers.
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 780.0;
+
+/// Wavelengths followed by each path, spread evenly across the range from a random hero.
+const COUNT: usize = 3;
+
+/// CIE XYZ to linear sRGB.
+const XYZ_TO_SRGB: [[f64; 3]; 3] = [
+    [3.2406, -1.5372, -0.4986],
+    [-0.9689, 1.8758, 0.0415],
+    [0.0557, -0.2040, 1.0570],
+];
+
+/// Converts between linear RGB colors and spectra.
+///
+/// Colors are uplifted to smooth spectra made of three overlapping bands, blue, green and red,
+/// that add up to one everywhere. Their weights are picked so the spectrum converts back to the
+/// same color, white is uplifted to a constant spectrum and converts back to white.
+pub struct Spectral {
+    /// RGB to band weights
+    to_weights: [[f64; 3]; 3],
+    /// Integral of each channel's matching function, to keep a constant spectrum white
+    white: [f64; 3],
+}
+
+impl Default for Spectral {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Spectral {
+    pub fn new() -> Self {
+        let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+        let lambdas = (0..steps).map(|i| LAMBDA_MIN + i as f64 + 0.5);
+
+        let mut white = [0.0; 3];
+        for lambda in lambdas.clone() {
+            let rgb = srgb_matching(lambda);
+            for c in 0..3 {
+                white[c] += rgb[c];
+            }
+        }
+        // color of each band, with matching functions that keep a constant spectrum white
+        let mut to_rgb = [[0.0; 3]; 3];
+        for lambda in lambdas {
+            let rgb = srgb_matching(lambda);
+            let bands = bands(lambda);
+            for c in 0..3 {
+                for k in 0..3 {
+                    to_rgb[c][k] += bands[k] * rgb[c] / white[c];
+                }
+            }
+        }
+
+        Self {
+            to_weights: inverse3(&to_rgb),
+            white,
+        }
+    }
+
+    /// Wavelengths for a path from `u` uniform in [0, 1), each uniformly distributed on its own.
+    pub fn sample(&self, u: f64) -> Wavelengths {
+        let lambda: [f64; COUNT] = std::array::from_fn(|i| {
+            let offset = (u + i as f64 / COUNT as f64).fract();
+            LAMBDA_MIN + offset * (LAMBDA_MAX - LAMBDA_MIN)
+        });
+        let uplift = lambda.map(|lambda| {
+            let bands = bands(lambda);
+            std::array::from_fn(|c| (0..3).map(|k| bands[k] * self.to_weights[k][c]).sum())
+        });
+        Wavelengths { lambda, uplift }
+    }
+
+    /// Linear sRGB estimate of the spectrum a path found `values` of at its wavelengths.
+    pub fn to_rgb(&self, wavelengths: &Wavelengths, values: &Color) -> Color {
+        let values = [values.r, values.g, values.b];
+        // divided by the density of each wavelength and averaged over them
+        let scale = (LAMBDA_MAX - LAMBDA_MIN) / COUNT as f64;
+        let mut rgb = [0.0; 3];
+        for (lambda, value) in wavelengths.lambda.iter().zip(values) {
+            let matching = srgb_matching(*lambda);
+            for c in 0..3 {
+                rgb[c] += scale * value * matching[c] / self.white[c];
+            }
+        }
+        Color::new(rgb[0], rgb[1], rgb[2])
+    }
+}
+
+/// Wavelengths a path carries light at. Colors along the path hold the values at each
+/// wavelength in place of red, green and blue, the hero wavelength's in red.
+#[derive(Clone, Copy)]
+pub struct Wavelengths {
+    /// Nanometers, the hero first
+    pub lambda: [f64; COUNT],
+    /// Value at each wavelength of the spectra uplifted from pure red, green and blue
+    uplift: [[f64; 3]; COUNT],
+}
+
+impl Wavelengths {
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    /// Values at the wavelengths of the spectrum of a linear RGB color.
+    /// Saturated colors can call for negative amounts of some wavelengths, which are clipped.
+    pub fn uplift(&self, color: &Color) -> Color {
+        let value = |u: &[f64; 3]| (u[0] * color.r + u[1] * color.g + u[2] * color.b).max(0.0);
+        Color::new(
+            value(&self.uplift[0]),
+            value(&self.uplift[1]),
+            value(&self.uplift[2]),
+        )
+    }
+
+    /// Weight that drops all but the hero wavelength from a path, which then stands in for all
+    /// of them. For paths that split by wavelength, like light refracted by a prism.
+    pub fn hero_only() -> Color {
+        Color::new(COUNT as f64, 0.0, 0.0)
+    }
+}
+
+/// Blue, green and red bands, smoothly overlapping around 490 and 590 nanometers.
+fn bands(lambda: f64) -> [f64; 3] {
+    let step = |edge: f64| 1.0 / (1.0 + (-(lambda - edge) / 10.0).exp());
+    let blue = 1.0 - step(490.0);
+    let red = step(590.0);
+    [red, 1.0 - blue - red, blue]
+}
+
+/// Linear sRGB of a unit of light at one wavelength, from the CIE 1931 matching functions.
+fn srgb_matching(lambda: f64) -> [f64; 3] {
+    let xyz = xyz_matching(lambda);
+    XYZ_TO_SRGB.map(|row| row[0] * xyz[0] + row[1] * xyz[1] + row[2] * xyz[2])
+}
+
+/// CIE 1931 standard observer, from the multi-lobe fit by Wyman, Sloan and Shirley.
+fn xyz_matching(lambda: f64) -> [f64; 3] {
+    // gaussian with a different width on each side of its peak
+    let g = |mu: f64, below: f64, above: f64| {
+        let t = (lambda - mu) / if lambda < mu { below } else { above };
+        (-0.5 * t * t).exp()
+    };
+    [
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    ]
+}
+
+fn inverse3(m: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let cofactor = |r: usize, c: usize| {
+        let (r0, r1) = ((r + 1) % 3, (r + 2) % 3);
+        let (c0, c1) = ((c + 1) % 3, (c + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let det = (0..3).map(|c| m[0][c] * cofactor(0, c)).sum::<f64>();
+    let mut inverse = [[0.0; 3]; 3];
+    for (r, row) in inverse.iter_mut().enumerate() {
+        for (c, value) in row.iter_mut().enumerate() {
+            *value = cofactor(c, r) / det;
+        }
+    }
+    inverse
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Average over evenly spread hero wavelengths of uplifting `color` and converting it back.
+    fn round_trip(spectral: &Spectral, color: &Color) -> Color {
+        let count = 10_000;
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        for i in 0..count {
+            let wavelengths = spectral.sample((i as f64 + 0.5) / count as f64);
+            sum += spectral.to_rgb(&wavelengths, &wavelengths.uplift(color));
+        }
+        sum.avg_by_samples(count)
+    }
+
+    #[test]
+    fn colors_round_trip() {
+        let spectral = Spectral::new();
+        for color in [Color::new(1.0, 1.0, 1.0), Color::new(0.2, 0.5, 0.8)] {
+            let back = round_trip(&spectral, &color);
+            for (value, expected) in [(back.r, color.r), (back.g, color.g), (back.b, color.b)] {
+                assert!((value - expected).abs() < 1.0e-2, "{} {}", value, expected);
+            }
+        }
+    }
+}
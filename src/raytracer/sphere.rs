@@ -1,18 +1,29 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
 
+use rand::rngs::SmallRng;
+use rand::Rng;
+
+use super::aabb::Aabb;
 use super::interval::Interval;
 use super::materials::Material;
+use super::motion::Motion;
+use super::onb::Onb;
 use super::ray::*;
-use super::vec3::Vec3;
+use super::vec3::*;
 
 pub struct Sphere {
-    center: Vec3,
+    center: Motion,
     radius: f64,
     material: Arc<Material>,
 }
 
 impl Sphere {
     pub fn new(center: Vec3, radius: f64, material: Arc<Material>) -> Self {
+        Self::moving(Motion::fixed(center), radius, material)
+    }
+
+    pub fn moving(center: Motion, radius: f64, material: Arc<Material>) -> Self {
         Self {
             center,
             radius,
@@ -21,9 +32,28 @@ impl Sphere {
     }
 }
 
+/// Longitude and latitude of a point on the unit sphere, `u` going around from -x towards +z
+/// and `v` from the bottom to the top.
+fn sphere_uv(point: &Vec3) -> (f64, f64) {
+    let theta = (-point.y).clamp(-1.0, 1.0).acos();
+    let phi = (-point.z).atan2(point.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
+
+/// Derivatives of the point on a sphere of `radius` by the coordinates of `sphere_uv`,
+/// `point` being on the unit sphere.
+fn sphere_uv_derivatives(point: &Vec3, radius: f64) -> (Vec3, Vec3) {
+    let dpdu = 2.0 * PI * radius * vec3!(point.z, 0.0, -point.x);
+    // the latitude circles shrink to nothing at the poles
+    let ring = (point.x * point.x + point.z * point.z).sqrt().max(1.0e-6);
+    let dpdv = PI * radius * vec3!(-point.y * point.x / ring, ring, -point.y * point.z / ring);
+    (dpdu, dpdv)
+}
+
 impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
-        let oc = ray.origin - self.center;
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let center = self.center.at(ray.time);
+        let oc = ray.origin - center;
         let a = ray.direction.length_squared();
         let half_b = oc.dot(&ray.direction);
         let c = oc.length_squared() - self.radius * self.radius;
@@ -43,7 +73,55 @@ impl Hittable for Sphere {
         }
 
         let p = ray.at(root);
-        let normal = (p - self.center) / self.radius;
-        return Some(HitRecord::new(root, ray, p, normal, &self.material));
+        let normal = (p - center) / self.radius;
+        let (u, v) = sphere_uv(&normal);
+        let (dpdu, dpdv) = sphere_uv_derivatives(&normal, self.radius);
+        Some(
+            HitRecord::new(root, ray, p, normal, &self.material)
+                .with_uv(u, v)
+                .with_uv_derivatives(dpdu, dpdv)
+                .with_normal_derivatives(dpdu / self.radius, dpdv / self.radius),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // covers the whole path
+        let bbox = self.center.bounds();
+        let d = 2.0 * self.radius;
+        Aabb::new(bbox.x.expand(d), bbox.y.expand(d), bbox.z.expand(d))
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    /// Uniform over the cone of directions the sphere covers, or over all directions from inside it.
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3, time: f64) -> f64 {
+        let ray = Ray::new(*origin, *direction, time);
+        if self
+            .hit(&ray, Interval::new(0.001, f64::INFINITY))
+            .is_none()
+        {
+            return 0.0;
+        }
+        let distance_squared = (self.center.at(time) - origin).length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return 1.0 / (4.0 * PI);
+        }
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
+
+    fn random(&self, origin: &Vec3, time: f64, rng: &mut SmallRng) -> Vec3 {
+        let direction = self.center.at(time) - origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return Vec3::random_unit_vec(rng);
+        }
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        let z = 1.0 + rng.gen::<f64>() * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * rng.gen::<f64>();
+        let sin_theta = (1.0 - z * z).sqrt();
+        Onb::new(&direction).local(&vec3!(phi.cos() * sin_theta, phi.sin() * sin_theta, z))
     }
 }
@@ -0,0 +1,221 @@
+use std::sync::Arc;
+
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use super::color::Color;
+use super::input::Image;
+use super::mipmap::MipMap;
+use super::ray::HitRecord;
+use super::vec3::*;
+
+pub trait Texture: Send + Sync {
+    /// Color at the hit point, which may be averaged over the area of the pixel it is seen in.
+    fn value(&self, hit_record: &HitRecord) -> Color;
+}
+
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _hit_record: &HitRecord) -> Color {
+        self.color
+    }
+}
+
+/// Alternates between two textures in cubes of `size` filling space.
+pub struct Checker {
+    size: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl Checker {
+    pub fn new(size: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self { size, even, odd }
+    }
+
+    pub fn from_colors(size: f64, even: Color, odd: Color) -> Self {
+        Self::new(
+            size,
+            Arc::new(SolidColor::new(even)),
+            Arc::new(SolidColor::new(odd)),
+        )
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, hit_record: &HitRecord) -> Color {
+        let cell = |x: f64| (x / self.size).floor() as i64;
+        let p = &hit_record.point;
+        if (cell(p.x) + cell(p.y) + cell(p.z)).rem_euclid(2) == 0 {
+            self.even.value(hit_record)
+        } else {
+            self.odd.value(hit_record)
+        }
+    }
+}
+
+const POINT_COUNT: usize = 256;
+
+/// Gradient noise over space, smoothly varying in about [-1, 1] with features roughly one unit apart.
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| Vec3::random_unit_vec(&mut rng))
+            .collect();
+        let mut permutation = || {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            p.shuffle(&mut rng);
+            p
+        };
+        let (perm_x, perm_y, perm_z) = (permutation(), permutation(), permutation());
+        Self {
+            gradients,
+            perm_x,
+            perm_y,
+            perm_z,
+        }
+    }
+
+    pub fn noise(&self, point: &Vec3) -> f64 {
+        let (fx, fy, fz) = (point.x.floor(), point.y.floor(), point.z.floor());
+        let (u, v, w) = (point.x - fx, point.y - fy, point.z - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+        // Hermite smoothing hides the grid in the interpolation
+        let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+        let (su, sv, sw) = (smooth(u), smooth(v), smooth(w));
+        let wrap = |n: i64| (n & (POINT_COUNT as i64 - 1)) as usize;
+
+        let mut sum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = &self.gradients[self.perm_x[wrap(i + di)]
+                        ^ self.perm_y[wrap(j + dj)]
+                        ^ self.perm_z[wrap(k + dk)]];
+                    let (di, dj, dk) = (di as f64, dj as f64, dk as f64);
+                    let weight = vec3!(u - di, v - dj, w - dk);
+                    sum += (di * su + (1.0 - di) * (1.0 - su))
+                        * (dj * sv + (1.0 - dj) * (1.0 - sv))
+                        * (dk * sw + (1.0 - dk) * (1.0 - sw))
+                        * gradient.dot(&weight);
+                }
+            }
+        }
+        sum
+    }
+
+    /// Sum of `depth` octaves of noise, each at double the frequency and half the weight.
+    pub fn turbulence(&self, point: &Vec3, depth: usize) -> f64 {
+        let mut sum = 0.0;
+        let mut p = *point;
+        let mut weight = 1.0;
+        for _ in 0..depth {
+            sum += weight * self.noise(&p);
+            weight *= 0.5;
+            p = 2.0 * p;
+        }
+        sum.abs()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum NoiseKind {
+    /// Plain smooth noise
+    Perlin,
+    /// Several octaves of noise, like clouds or smoke
+    Turbulence,
+    /// Stripes across z distorted by turbulence
+    Marble,
+}
+
+/// Solid texture blending between two colors by Perlin noise.
+pub struct Noise {
+    perlin: Perlin,
+    kind: NoiseKind,
+    /// Frequency, higher values give smaller features
+    scale: f64,
+    low: Color,
+    high: Color,
+}
+
+impl Noise {
+    /// Black to white, `seed` picks the pattern.
+    pub fn new(kind: NoiseKind, scale: f64, seed: u64) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            kind,
+            scale,
+            low: Color::new(0.0, 0.0, 0.0),
+            high: Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn with_colors(mut self, low: Color, high: Color) -> Self {
+        self.low = low;
+        self.high = high;
+        self
+    }
+}
+
+impl Texture for Noise {
+    fn value(&self, hit_record: &HitRecord) -> Color {
+        let point = &hit_record.point;
+        let p = self.scale * point;
+        let t = match self.kind {
+            NoiseKind::Perlin => 0.5 * (1.0 + self.perlin.noise(&p)),
+            NoiseKind::Turbulence => self.perlin.turbulence(&p, 7),
+            // the frequency only applies to the stripes, not to their distortion
+            NoiseKind::Marble => {
+                0.5 * (1.0 + (p.z + 10.0 * self.perlin.turbulence(point, 7)).sin())
+            }
+        }
+        .clamp(0.0, 1.0);
+        (1.0 - t) * self.low + t * self.high
+    }
+}
+
+/// Image stretched over the surface coordinates with `v` going up, repeating outside [0, 1].
+/// Filtered over the pixel's footprint where it is known.
+pub struct ImageTexture {
+    mipmap: MipMap,
+}
+
+impl ImageTexture {
+    pub fn new(image: Image) -> Self {
+        Self {
+            mipmap: MipMap::new(image),
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, hit_record: &HitRecord) -> Color {
+        let (dudx, dvdx) = hit_record.duvdx;
+        let (dudy, dvdy) = hit_record.duvdy;
+        self.mipmap.filter(
+            hit_record.u,
+            1.0 - hit_record.v,
+            (dudx, -dvdx),
+            (dudy, -dvdy),
+        )
+    }
+}
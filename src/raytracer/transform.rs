@@ -0,0 +1,208 @@
+use auto_ops::*;
+
+use super::vec3::*;
+
+/// Affine transformation matrix, acting on column vectors.
+#[derive(Clone, Copy)]
+pub struct Mat4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub fn new(m: [[f64; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        Self::new([
+            [factors.x, 0.0, 0.0, 0.0],
+            [0.0, factors.y, 0.0, 0.0],
+            [0.0, 0.0, factors.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Counter-clockwise when looking down `axis` towards the origin.
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        let a = axis.unit_vec();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let t = 1.0 - cos;
+        Self::new([
+            [
+                t * a.x * a.x + cos,
+                t * a.x * a.y - sin * a.z,
+                t * a.x * a.z + sin * a.y,
+                0.0,
+            ],
+            [
+                t * a.x * a.y + sin * a.z,
+                t * a.y * a.y + cos,
+                t * a.y * a.z - sin * a.x,
+                0.0,
+            ],
+            [
+                t * a.x * a.z - sin * a.y,
+                t * a.y * a.z + sin * a.x,
+                t * a.z * a.z + cos,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Self::new(m)
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting, none for singular matrices.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Self::IDENTITY.m;
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1.0e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[col][j];
+                        inv[row][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+        Some(Self::new(inv))
+    }
+
+    /// Determinant of the linear part, how much volumes are scaled.
+    pub fn determinant3(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn point(&self, p: &Vec3) -> Vec3 {
+        let m = &self.m;
+        vec3!(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3]
+        )
+    }
+
+    /// Ignores the translation.
+    pub fn vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        vec3!(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z
+        )
+    }
+}
+
+impl_op_ex!(*|a: &Mat4, b: &Mat4| -> Mat4 {
+    let mut m = [[0.0; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a.m[i][k] * b.m[k][j]).sum();
+        }
+    }
+    Mat4::new(m)
+});
+
+/// Matrix together with its inverse, for moving between object and world space.
+#[derive(Clone, Copy)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+    /// Inverse transpose, keeps normals perpendicular to transformed surfaces
+    normal_matrix: Mat4,
+}
+
+impl Transform {
+    /// Fails for matrices that can not be inverted, e.g. scaling by zero.
+    pub fn new(matrix: Mat4) -> Result<Self, String> {
+        let inverse = matrix
+            .inverse()
+            .ok_or_else(|| "transform can not be inverted".to_string())?;
+        Ok(Self {
+            matrix,
+            inverse,
+            normal_matrix: inverse.transpose(),
+        })
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> &Mat4 {
+        &self.inverse
+    }
+
+    /// Not normalized.
+    pub fn normal(&self, n: &Vec3) -> Vec3 {
+        self.normal_matrix.vector(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        let matrix = Mat4::translation(vec3!(1.0, -2.0, 3.5))
+            * Mat4::rotation(vec3!(1.0, 2.0, -0.5), 37.0)
+            * Mat4::scaling(vec3!(2.0, 0.5, -3.0));
+        let inverse = matrix.inverse().unwrap();
+        for product in [matrix * inverse, inverse * matrix] {
+            for (row, identity) in product.m.iter().zip(Mat4::IDENTITY.m) {
+                for (value, expected) in row.iter().zip(identity) {
+                    assert!((value - expected).abs() < 1.0e-12);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        assert!(Mat4::scaling(vec3!(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+}
@@ -0,0 +1,505 @@
+use std::fmt;
+use std::sync::Arc;
+
+use rand::rngs::SmallRng;
+use rand::Rng;
+
+use super::aabb::Aabb;
+use super::bvh::BvhNode;
+use super::distribution::Distribution1D;
+use super::interval::Interval;
+use super::materials::Material;
+use super::ray::*;
+use super::vec3::Vec3;
+
+/// Möller–Trumbore intersection, returns `t` and the barycentric weights of `p1` and `p2`.
+pub fn intersect(
+    ray: &Ray,
+    ray_t: &Interval,
+    p0: &Vec3,
+    p1: &Vec3,
+    p2: &Vec3,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+    let pvec = ray.direction.cross(&edge2);
+    let det = edge1.dot(&pvec);
+    if det.abs() < 1.0e-12 {
+        // ray parallel to the triangle
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = ray.origin - p0;
+    let b1 = tvec.dot(&pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(&edge1);
+    let b2 = ray.direction.dot(&qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(&qvec) * inv_det;
+    if !ray_t.surrounds(t) {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+fn area(p0: &Vec3, p1: &Vec3, p2: &Vec3) -> f64 {
+    0.5 * (p1 - p0).cross(&(p2 - p0)).length()
+}
+
+/// Uniformly distributed point on a triangle.
+fn random_point(p0: &Vec3, p1: &Vec3, p2: &Vec3, rng: &mut SmallRng) -> Vec3 {
+    let s = rng.gen::<f64>().sqrt();
+    let b2 = rng.gen::<f64>();
+    (1.0 - s) * p0 + s * (1.0 - b2) * p1 + s * b2 * p2
+}
+
+/// Derivatives by the texture coordinates of a value interpolated across a triangle,
+/// e.g. the position, zero where the coordinates do not span an area.
+fn uv_derivatives(values: [&Vec3; 3], uvs: [(f64, f64); 3]) -> (Vec3, Vec3) {
+    let [a0, a1, a2] = values;
+    let [uv0, uv1, uv2] = uvs;
+    let (du02, dv02) = (uv0.0 - uv2.0, uv0.1 - uv2.1);
+    let (du12, dv12) = (uv1.0 - uv2.0, uv1.1 - uv2.1);
+    let det = du02 * dv12 - dv02 * du12;
+    if det.abs() < 1.0e-12 {
+        return (Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0));
+    }
+    let (da02, da12) = (a0 - a2, a1 - a2);
+    (
+        (dv12 * da02 - dv02 * da12) / det,
+        (du02 * da12 - du12 * da02) / det,
+    )
+}
+
+/// Density of uniformly picking the hit point on a surface of `area`,
+/// converted to solid angle as seen along `direction`.
+fn solid_angle_pdf(direction: &Vec3, hit: &HitRecord, area: f64) -> f64 {
+    let distance_squared = hit.t * hit.t * direction.length_squared();
+    let cosine = direction.unit_vec().dot(&hit.geometric_normal).abs();
+    if cosine == 0.0 {
+        return 0.0;
+    }
+    distance_squared / (cosine * area)
+}
+
+pub struct Triangle {
+    vertices: [Vec3; 3],
+    material: Arc<Material>,
+}
+
+impl Triangle {
+    /// The front face is the one the vertices appear counter-clockwise from.
+    pub fn new(p0: Vec3, p1: Vec3, p2: Vec3, material: Arc<Material>) -> Self {
+        Self {
+            vertices: [p0, p1, p2],
+            material,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let [p0, p1, p2] = &self.vertices;
+        let (t, b1, b2) = intersect(ray, &ray_t, p0, p1, p2)?;
+        let normal = (p1 - p0).cross(&(p2 - p0)).unit_vec();
+        Some(
+            HitRecord::new(t, ray, ray.at(t), normal, &self.material)
+                .with_uv(b1, b2)
+                .with_uv_derivatives(p1 - p0, p2 - p0),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [p0, p1, p2] = &self.vertices;
+        Aabb::enclosing(&Aabb::from_points(*p0, *p1), &Aabb::from_points(*p2, *p2)).padded()
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3, time: f64) -> f64 {
+        let ray = Ray::new(*origin, *direction, time);
+        match self.hit(&ray, Interval::new(0.001, f64::INFINITY)) {
+            Some(hit) => {
+                let [p0, p1, p2] = &self.vertices;
+                solid_angle_pdf(direction, &hit, area(p0, p1, p2))
+            }
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Vec3, _time: f64, rng: &mut SmallRng) -> Vec3 {
+        let [p0, p1, p2] = &self.vertices;
+        random_point(p0, p1, p2, rng) - origin
+    }
+}
+
+/// Vertex data shared by all triangles of a mesh.
+struct MeshData {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    indices: Vec<[usize; 3]>,
+    material: Arc<Material>,
+}
+
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    index: usize,
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let mesh = &self.mesh;
+        let [i0, i1, i2] = mesh.indices[self.index];
+        let (p0, p1, p2) = (
+            &mesh.positions[i0],
+            &mesh.positions[i1],
+            &mesh.positions[i2],
+        );
+        let (t, b1, b2) = intersect(ray, &ray_t, p0, p1, p2)?;
+        let b0 = 1.0 - b1 - b2;
+
+        // barycentric coordinates stand in for missing texture coordinates
+        let uvs = if mesh.uvs.is_empty() {
+            [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]
+        } else {
+            [mesh.uvs[i0], mesh.uvs[i1], mesh.uvs[i2]]
+        };
+        let [uv0, uv1, uv2] = uvs;
+        let (u, v) = (
+            b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+            b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+        );
+        let (dpdu, dpdv) = uv_derivatives([p0, p1, p2], uvs);
+
+        let mut normal = (p1 - p0).cross(&(p2 - p0)).unit_vec();
+        let interpolated = (!mesh.normals.is_empty())
+            .then(|| b0 * mesh.normals[i0] + b1 * mesh.normals[i1] + b2 * mesh.normals[i2])
+            // opposed vertex normals can cancel out, leaving the surface's own
+            .filter(|n| !n.near_zero());
+        let (shading_normal, dndu, dndv) = if let Some(n) = interpolated {
+            let n = n.unit_vec();
+            // the authored normals decide which side is the front
+            if n.dot(&normal) < 0.0 {
+                normal = -normal;
+            }
+            let normals = [&mesh.normals[i0], &mesh.normals[i1], &mesh.normals[i2]];
+            let (dndu, dndv) = uv_derivatives(normals, uvs);
+            (n, dndu, dndv)
+        } else {
+            let zero = Vec3::new(0.0, 0.0, 0.0);
+            (normal, zero, zero)
+        };
+
+        Some(
+            HitRecord::new(t, ray, ray.at(t), normal, &mesh.material)
+                .with_shading_normal(shading_normal)
+                .with_uv(u, v)
+                .with_uv_derivatives(dpdu, dpdv)
+                .with_normal_derivatives(dndu, dndv),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [i0, i1, i2] = self.mesh.indices[self.index];
+        let positions = &self.mesh.positions;
+        Aabb::enclosing(
+            &Aabb::from_points(positions[i0], positions[i1]),
+            &Aabb::from_points(positions[i2], positions[i2]),
+        )
+        .padded()
+    }
+}
+
+/// Mesh data that does not fit together, naming the argument of `TriangleMesh::new` at fault.
+pub struct MeshError {
+    pub argument: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.argument, self.message)
+    }
+}
+
+/// Indexed triangle mesh. Normals and UVs are per vertex and may be left empty,
+/// the triangles are kept in their own hierarchy.
+pub struct TriangleMesh {
+    mesh: Arc<MeshData>,
+    triangles: Box<dyn Hittable>,
+    /// For picking triangles by area when the mesh is sampled as a light
+    areas: Distribution1D,
+    area: f64,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Vec3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        indices: Vec<[usize; 3]>,
+        material: Arc<Material>,
+    ) -> Result<Self, MeshError> {
+        let error = |argument, message| Err(MeshError { argument, message });
+        if !normals.is_empty() && normals.len() != positions.len() {
+            return error(
+                "normals",
+                format!("{} given for {} vertices", normals.len(), positions.len()),
+            );
+        }
+        if !uvs.is_empty() && uvs.len() != positions.len() {
+            return error(
+                "uvs",
+                format!("{} given for {} vertices", uvs.len(), positions.len()),
+            );
+        }
+        if let Some(i) = indices.iter().flatten().find(|&&i| i >= positions.len()) {
+            return error(
+                "indices",
+                format!("vertex {} out of range for {} vertices", i, positions.len()),
+            );
+        }
+
+        let areas: Vec<f64> = indices
+            .iter()
+            .map(|&[i0, i1, i2]| area(&positions[i0], &positions[i1], &positions[i2]))
+            .collect();
+        let total_area = areas.iter().sum();
+        let mesh = Arc::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            indices,
+            material,
+        });
+        let triangles = (0..mesh.indices.len())
+            .map(|index| {
+                Box::new(MeshTriangle {
+                    mesh: mesh.clone(),
+                    index,
+                }) as Box<dyn Hittable>
+            })
+            .collect();
+
+        Ok(Self {
+            mesh,
+            triangles: BvhNode::build(triangles),
+            areas: Distribution1D::new(areas),
+            area: total_area,
+        })
+    }
+
+    #[cfg(test)]
+    pub fn triangle_count(&self) -> usize {
+        self.mesh.indices.len()
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.triangles.hit(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.triangles.bounding_box()
+    }
+
+    fn is_light(&self) -> bool {
+        self.mesh.material.is_emissive() && self.area > 0.0
+    }
+
+    /// Sums over every triangle in `direction`, as `random` could have picked any of them.
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3, time: f64) -> f64 {
+        let ray = Ray::new(*origin, *direction, time);
+        let mut pdf = 0.0;
+        let mut t_min = 0.001;
+        while let Some(hit) = self
+            .triangles
+            .hit(&ray, Interval::new(t_min, f64::INFINITY))
+        {
+            pdf += solid_angle_pdf(direction, &hit, self.area);
+            t_min = hit.t;
+        }
+        pdf
+    }
+
+    fn random(&self, origin: &Vec3, _time: f64, rng: &mut SmallRng) -> Vec3 {
+        let (_, _, index) = self.areas.sample(rng.gen());
+        let [i0, i1, i2] = self.mesh.indices[index];
+        let positions = &self.mesh.positions;
+        random_point(&positions[i0], &positions[i1], &positions[i2], rng) - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracer::color::Color;
+    use crate::raytracer::materials::Lambertian;
+    use crate::raytracer::vec3::*;
+
+    fn material() -> Arc<Material> {
+        Arc::new(Material::Lambertian(Lambertian::new(Color::new(
+            0.5, 0.5, 0.5,
+        ))))
+    }
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!(
+            (actual - expected).length() < 1.0e-9,
+            "{:?} is not {:?}",
+            (actual.x, actual.y, actual.z),
+            (expected.x, expected.y, expected.z)
+        );
+    }
+
+    /// Ray straight down onto the plane z = 0 at (x, y).
+    fn down_at(x: f64, y: f64) -> Ray {
+        Ray::new(vec3!(x, y, 2.0), vec3!(0.0, 0.0, -1.0), 0.0)
+    }
+
+    /// Right triangle in the plane z = 0, facing up.
+    fn corners() -> [Vec3; 3] {
+        [
+            vec3!(0.0, 0.0, 0.0),
+            vec3!(1.0, 0.0, 0.0),
+            vec3!(0.0, 1.0, 0.0),
+        ]
+    }
+
+    fn single_triangle(normals: Vec<Vec3>, uvs: Vec<(f64, f64)>) -> TriangleMesh {
+        TriangleMesh::new(
+            corners().to_vec(),
+            normals,
+            uvs,
+            vec![[0, 1, 2]],
+            material(),
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    #[test]
+    fn intersect_finds_distance_and_barycentric_weights() {
+        let [p0, p1, p2] = corners();
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let (t, b1, b2) = intersect(&down_at(0.25, 0.5), &ray_t, &p0, &p1, &p2).unwrap();
+        assert!((t - 2.0).abs() < 1.0e-12);
+        assert!((b1 - 0.25).abs() < 1.0e-12);
+        assert!((b2 - 0.5).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn intersect_misses_past_the_edges_and_along_the_plane() {
+        let [p0, p1, p2] = corners();
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        for (x, y) in [(0.6, 0.6), (-0.1, 0.5), (0.5, -0.1)] {
+            assert!(intersect(&down_at(x, y), &ray_t, &p0, &p1, &p2).is_none());
+        }
+        let along = Ray::new(vec3!(-1.0, 0.2, 0.0), vec3!(1.0, 0.0, 0.0), 0.0);
+        assert!(intersect(&along, &ray_t, &p0, &p1, &p2).is_none());
+        let short = Interval::new(0.001, 1.0);
+        assert!(intersect(&down_at(0.25, 0.25), &short, &p0, &p1, &p2).is_none());
+    }
+
+    #[test]
+    fn mesh_interpolates_texture_coordinates() {
+        let mesh = single_triangle(Vec::new(), vec![(0.5, 0.5), (1.0, 0.5), (0.5, 1.0)]);
+        let hit = mesh
+            .hit(&down_at(0.25, 0.5), Interval::new(0.001, f64::INFINITY))
+            .unwrap();
+        assert!((hit.u - 0.625).abs() < 1.0e-12);
+        assert!((hit.v - 0.75).abs() < 1.0e-12);
+        // half a unit of u or v spans the whole triangle
+        assert_close(hit.dpdu, vec3!(2.0, 0.0, 0.0));
+        assert_close(hit.dpdv, vec3!(0.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn uv_derivatives_follow_the_mapping() {
+        let [p0, p1, p2] = corners();
+        let (dpdu, dpdv) = uv_derivatives([&p0, &p1, &p2], [(0.0, 0.0), (0.0, 1.0), (1.0, 0.0)]);
+        assert_close(dpdu, p2);
+        assert_close(dpdv, p1);
+        let (dpdu, dpdv) = uv_derivatives([&p0, &p1, &p2], [(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)]);
+        assert_close(dpdu, vec3!(0.0, 0.0, 0.0));
+        assert_close(dpdv, vec3!(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn vertex_normals_decide_the_front() {
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let counter_clockwise = single_triangle(Vec::new(), Vec::new());
+        let hit = counter_clockwise.hit(&down_at(0.25, 0.25), ray_t).unwrap();
+        assert!(hit.front_face);
+        assert_close(hit.geometric_normal, vec3!(0.0, 0.0, 1.0));
+
+        let facing_down = single_triangle(vec![vec3!(0.0, 0.0, -1.0); 3], Vec::new());
+        let hit = facing_down.hit(&down_at(0.25, 0.25), ray_t).unwrap();
+        assert!(!hit.front_face);
+        assert_close(hit.geometric_normal, vec3!(0.0, 0.0, 1.0));
+        let up = Ray::new(vec3!(0.25, 0.25, -2.0), vec3!(0.0, 0.0, 1.0), 0.0);
+        let hit = facing_down.hit(&up, ray_t).unwrap();
+        assert!(hit.front_face);
+        assert_close(hit.geometric_normal, vec3!(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn mesh_rejects_data_that_does_not_fit() {
+        let argument = |normals: Vec<Vec3>, uvs: Vec<(f64, f64)>, indices: Vec<[usize; 3]>| {
+            match TriangleMesh::new(corners().to_vec(), normals, uvs, indices, material()) {
+                Ok(_) => panic!("mesh was accepted"),
+                Err(e) => e.argument,
+            }
+        };
+        assert_eq!(argument(Vec::new(), Vec::new(), vec![[0, 1, 3]]), "indices");
+        assert_eq!(
+            argument(vec![vec3!(0.0, 0.0, 1.0); 2], Vec::new(), vec![[0, 1, 2]]),
+            "normals"
+        );
+        assert_eq!(
+            argument(Vec::new(), vec![(0.0, 0.0); 4], vec![[0, 1, 2]]),
+            "uvs"
+        );
+    }
+
+    #[test]
+    fn quad_pdf_is_uniform_over_its_area() {
+        let quad = TriangleMesh::new(
+            vec![
+                vec3!(-1.0, -1.0, 0.0),
+                vec3!(1.0, -1.0, 0.0),
+                vec3!(1.0, 1.0, 0.0),
+                vec3!(-1.0, 1.0, 0.0),
+            ],
+            Vec::new(),
+            Vec::new(),
+            vec![[0, 1, 2], [0, 2, 3]],
+            material(),
+        )
+        .unwrap_or_else(|e| panic!("{}", e));
+        let origin = vec3!(0.0, 0.0, 3.0);
+        for direction in [vec3!(0.5, 0.25, -3.0), vec3!(-0.4, 0.9, -3.0)] {
+            let distance_squared = direction.length_squared();
+            let cosine = 3.0 / distance_squared.sqrt();
+            let expected = distance_squared / (cosine * 4.0);
+            let pdf = quad.pdf_value(&origin, &direction, 0.0);
+            assert!(
+                (pdf - expected).abs() < 1.0e-9,
+                "{} is not {}",
+                pdf,
+                expected
+            );
+        }
+        assert_eq!(quad.pdf_value(&origin, &vec3!(2.0, 0.0, -3.0), 0.0), 0.0);
+    }
+}
@@ -1,5 +1,7 @@
+use std::ops::Index;
+
 use auto_ops::*;
-use rand::{rngs::ThreadRng, Rng};
+use rand::{rngs::SmallRng, Rng};
 
 #[derive(Clone, Copy)]
 pub struct Vec3 {
@@ -51,7 +53,7 @@ impl Vec3 {
     }
 
     pub fn reflect(&self, normal: &Vec3) -> Self {
-        return self - 2.0 * self.dot(normal) * normal;
+        self - 2.0 * self.dot(normal) * normal
     }
 
     pub fn refract(&self, n: &Self, etai_over_etat: f64) -> Self {
@@ -61,7 +63,7 @@ impl Vec3 {
         r_out_perp + r_out_parallel
     }
 
-    fn random(min: f64, max: f64, rng: &mut ThreadRng) -> Self {
+    fn random(min: f64, max: f64, rng: &mut SmallRng) -> Self {
         Vec3::new(
             min + (max - min) * rng.gen::<f64>(),
             min + (max - min) * rng.gen::<f64>(),
@@ -69,7 +71,7 @@ impl Vec3 {
         )
     }
 
-    pub fn random_unit_vec(rng: &mut ThreadRng) -> Self {
+    pub fn random_unit_vec(rng: &mut SmallRng) -> Self {
         let mut v = Vec3::random(-1.0, 1.0, rng);
         loop {
             if v.length_squared() < 1.0 {
@@ -80,12 +82,34 @@ impl Vec3 {
         v.unit_vec()
     }
 
-    pub fn random_on_hemisphere(normal: &Vec3, rng: &mut ThreadRng) -> Self {
+    pub fn random_in_unit_disk(rng: &mut SmallRng) -> Self {
+        loop {
+            let v = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
+            if v.length_squared() < 1.0 {
+                return v;
+            }
+        }
+    }
+
+    pub fn random_on_hemisphere(normal: &Vec3, rng: &mut SmallRng) -> Self {
         let v = Self::random_unit_vec(rng);
         if v.dot(normal) > 0.0 {
-            return v;
+            v
         } else {
-            return -v;
+            -v
+        }
+    }
+}
+
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 axis out of range: {}", axis),
         }
     }
 }
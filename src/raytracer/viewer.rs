@@ -0,0 +1,60 @@
+extern crate sdl2;
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::PixelFormatEnum;
+
+use super::framebuffer::Framebuffer;
+
+/// Opens a window showing the rendered image until it is closed or Escape is pressed.
+pub fn show(title: &str, framebuffer: &Framebuffer) -> Result<(), String> {
+    let sdl_context = sdl2::init()?;
+    let video_subsystem = sdl_context.video()?;
+
+    let window = video_subsystem
+        .window(title, framebuffer.width as u32, framebuffer.height as u32)
+        .position_centered()
+        .opengl()
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    let texture_creator = canvas.texture_creator();
+
+    let mut texture = texture_creator
+        .create_texture_streaming(
+            PixelFormatEnum::RGB24,
+            framebuffer.width as u32,
+            framebuffer.height as u32,
+        )
+        .map_err(|e| e.to_string())?;
+
+    texture.with_lock(None, |buffer: &mut [u8], pitch| {
+        let row = framebuffer.width * 3;
+        let pixels = framebuffer.to_rgb8();
+        for (dst, src) in buffer.chunks_mut(pitch).zip(pixels.chunks(row)) {
+            dst[..row].copy_from_slice(src);
+        }
+    })?;
+
+    let mut event_pump = sdl_context.event_pump()?;
+
+    'running: loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'running,
+                _ => {}
+            }
+        }
+
+        canvas.clear();
+        canvas.copy(&texture, None, None)?;
+        canvas.present();
+    }
+
+    Ok(())
+}
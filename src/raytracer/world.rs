@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use rand::rngs::SmallRng;
+use rand::Rng;
+
+use super::aabb::Aabb;
+use super::bvh::BvhNode;
+use super::environment::Environment;
+use super::interval::Interval;
+use super::ray::*;
+use super::vec3::*;
+
+/// Everything rays can interact with.
+pub struct World {
+    pub objects: Box<dyn Hittable>,
+    /// Emissive objects, also part of `objects`
+    pub lights: Vec<Arc<dyn Hittable>>,
+    pub environment: Environment,
+}
+
+impl World {
+    pub fn new(objects: Vec<Box<dyn Hittable>>, environment: Environment) -> Self {
+        let mut lights = Vec::new();
+        let objects = share_lights(objects, &mut lights);
+        Self {
+            objects: BvhNode::build(objects),
+            lights,
+            environment,
+        }
+    }
+
+    /// Density of picking `direction` from `origin` by choosing a light at random and sampling it.
+    pub fn light_pdf(&self, origin: &Vec3, direction: &Vec3, time: f64) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self
+            .lights
+            .iter()
+            .map(|light| light.pdf_value(origin, direction, time))
+            .sum();
+        sum / self.lights.len() as f64
+    }
+}
+
+/// Objects built together and placed as one by instances, such as shared geometry.
+pub struct Group {
+    objects: Box<dyn Hittable>,
+    /// Every object, for sampling the group as a whole
+    members: Vec<Arc<dyn Hittable>>,
+    lights: Vec<Arc<dyn Hittable>>,
+}
+
+impl Group {
+    pub fn new(objects: Vec<Box<dyn Hittable>>) -> Self {
+        let members: Vec<Arc<dyn Hittable>> = objects.into_iter().map(Arc::from).collect();
+        let mut lights = Vec::new();
+        for member in &members {
+            if member.is_light() {
+                lights.push(member.clone());
+            } else {
+                lights.extend(member.lights());
+            }
+        }
+        let objects = members
+            .iter()
+            .map(|member| Box::new(member.clone()) as Box<dyn Hittable>)
+            .collect();
+        Self {
+            objects: BvhNode::build(objects),
+            members,
+            lights,
+        }
+    }
+}
+
+impl Hittable for Group {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.objects.hit(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.objects.bounding_box()
+    }
+
+    /// Density of picking a member at random and sampling it, as an instance giving the whole group
+    /// an emissive material does.
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3, time: f64) -> f64 {
+        if self.members.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self
+            .members
+            .iter()
+            .map(|member| member.pdf_value(origin, direction, time))
+            .sum();
+        sum / self.members.len() as f64
+    }
+
+    fn random(&self, origin: &Vec3, time: f64, rng: &mut SmallRng) -> Vec3 {
+        if self.members.is_empty() {
+            return vec3!(1.0, 0.0, 0.0);
+        }
+        let member = &self.members[rng.gen_range(0..self.members.len())];
+        member.random(origin, time, rng)
+    }
+
+    fn lights(&self) -> Vec<Arc<dyn Hittable>> {
+        self.lights.clone()
+    }
+}
+
+/// Adds the lights among `objects`, and those inside them, to `lights` as well.
+fn share_lights(
+    objects: Vec<Box<dyn Hittable>>,
+    lights: &mut Vec<Arc<dyn Hittable>>,
+) -> Vec<Box<dyn Hittable>> {
+    objects
+        .into_iter()
+        .map(|object| {
+            if object.is_light() {
+                let light: Arc<dyn Hittable> = Arc::from(object);
+                lights.push(light.clone());
+                Box::new(light)
+            } else {
+                lights.extend(object.lights());
+                object
+            }
+        })
+        .collect()
+}